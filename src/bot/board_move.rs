@@ -1,25 +1,38 @@
-use super::{BitBoard, Board, utils::{south_one, north_one}};
+use std::ops::{Deref, DerefMut};
+
+use super::{
+    utils::{north_one, parse_square, south_one, string_square},
+    BitBoard, Board,
+};
 
 impl Board {
-    pub fn make_move(self, book_move: Move) -> Board {
-        let mut res = self.clone();
-        let from_mask = (1 as BitBoard) << book_move.from_square;
-        let to_mask = (1 as BitBoard) << book_move.to_square;
+    //Applies a move from any source (search, book, GUI or UCI). Castling is the king's two square step, en passant
+    //and promotions are worked out from the board.
+    pub fn make_move(self, new_move: Move) -> Board {
+        let mut res = self;
+        let from_mask: BitBoard = 1 << new_move.from_square;
+        let to_mask: BitBoard = 1 << new_move.to_square;
         res.en_passant = 0;
+        res.castle &= !from_mask;
         if res.white_pawns & from_mask != 0 {
-            res.white_pawns ^= from_mask | to_mask;
+            res.white_pawns ^= from_mask;
             if to_mask & self.en_passant != 0 {
                 res.capture_black(south_one(to_mask));
             }
-            if book_move.to_square - book_move.from_square == 16 {
+            if new_move.to_square == new_move.from_square + 16 {
                 res.en_passant = south_one(to_mask);
             }
-            if book_move.to_square < 8 {
-                res.white_queens |= to_mask;
+            res.capture_black(to_mask);
+            if to_mask & EIGHTH_RANK != 0 {
+                match new_move.promotion_piece {
+                    PromotionPiece::Knight => res.white_knights |= to_mask,
+                    PromotionPiece::Bishop => res.white_bishops |= to_mask,
+                    PromotionPiece::Rook => res.white_rooks |= to_mask,
+                    PromotionPiece::Queen | PromotionPiece::None => res.white_queens |= to_mask,
+                }
             } else {
                 res.white_pawns |= to_mask;
             }
-            res.capture_black(to_mask);
         } else if res.white_knights & from_mask != 0 {
             res.white_knights ^= from_mask | to_mask;
             res.capture_black(to_mask);
@@ -33,14 +46,13 @@ impl Board {
             res.white_queens ^= from_mask | to_mask;
             res.capture_black(to_mask);
         } else if res.white_kings & from_mask != 0 {
-            if from_mask | to_mask == 0x90 {
+            res.castle &= !0xff;
+            if (from_mask == 0x10) & (to_mask == 0x40) {
                 res.white_kings ^= 0x50;
                 res.white_rooks ^= 0xa0;
-                res.castle &= !0xff;
-            } else if from_mask | to_mask == 0x11 {
+            } else if (from_mask == 0x10) & (to_mask == 0x4) {
                 res.white_kings ^= 0x14;
                 res.white_rooks ^= 0x9;
-                res.castle &= !0xff;
             } else {
                 res.white_kings ^= from_mask | to_mask;
                 res.capture_black(to_mask);
@@ -50,15 +62,20 @@ impl Board {
             if to_mask & self.en_passant != 0 {
                 res.capture_white(north_one(to_mask));
             }
-            if book_move.from_square - book_move.to_square == 16 {
+            if new_move.from_square == new_move.to_square + 16 {
                 res.en_passant = north_one(to_mask);
             }
-            if book_move.to_square < 8 {
-                res.black_queens |= to_mask;
+            res.capture_white(to_mask);
+            if to_mask & FIRST_RANK != 0 {
+                match new_move.promotion_piece {
+                    PromotionPiece::Knight => res.black_knights |= to_mask,
+                    PromotionPiece::Bishop => res.black_bishops |= to_mask,
+                    PromotionPiece::Rook => res.black_rooks |= to_mask,
+                    PromotionPiece::Queen | PromotionPiece::None => res.black_queens |= to_mask,
+                }
             } else {
                 res.black_pawns |= to_mask;
             }
-            res.capture_white(to_mask);
         } else if res.black_knights & from_mask != 0 {
             res.black_knights ^= from_mask | to_mask;
             res.capture_white(to_mask);
@@ -72,14 +89,13 @@ impl Board {
             res.black_queens ^= from_mask | to_mask;
             res.capture_white(to_mask);
         } else if res.black_kings & from_mask != 0 {
-            if from_mask | to_mask == 0x9000000000000000 {
+            res.castle &= !0xff00000000000000;
+            if (from_mask == 0x1000000000000000) & (to_mask == 0x4000000000000000) {
                 res.black_kings ^= 0x5000000000000000;
                 res.black_rooks ^= 0xa000000000000000;
-                res.castle &= !0xff00000000000000;
-            } else if from_mask | to_mask == 0x1100000000000000 {
+            } else if (from_mask == 0x1000000000000000) & (to_mask == 0x400000000000000) {
                 res.black_kings ^= 0x1400000000000000;
                 res.black_rooks ^= 0x900000000000000;
-                res.castle &= !0xff00000000000000;
             } else {
                 res.black_kings ^= from_mask | to_mask;
                 res.capture_white(to_mask);
@@ -87,19 +103,148 @@ impl Board {
         }
         res.redo_occupied();
         res.white_to_play = !res.white_to_play;
+        let capture = res.occupied.count_ones() < self.occupied.count_ones();
+        if ((self.white_pawns | self.black_pawns) & from_mask != 0) | capture {
+            res.halfmove_clock = 0;
+        } else {
+            res.halfmove_clock = self.halfmove_clock + 1;
+        }
+        if !self.white_to_play {
+            res.fullmove_number = self.fullmove_number + 1;
+        }
+        res.update_zobrist(&self, new_move);
         res
     }
 }
 
-#[derive(Clone, Copy)]
+const FIRST_RANK: BitBoard = 0x00000000000000FF;
+const EIGHTH_RANK: BitBoard = 0xFF00000000000000;
+
+#[derive(Clone, Copy, Debug)]
 pub struct Move {
     pub to_square: u16,
     pub from_square: u16,
     pub promotion_piece: PromotionPiece,
+    pub flags: u8,
     pub weight: u16,
 }
 
+impl Move {
+    pub const QUIET: u8 = 0;
+    pub const CAPTURE: u8 = 1;
+    pub const CASTLE: u8 = 2;
+    pub const EN_PASSANT: u8 = 4;
+    pub const DOUBLE_PUSH: u8 = 8;
+
+    pub const fn new(from: usize, to: usize, promotion_piece: PromotionPiece, flags: u8) -> Self {
+        Move {
+            to_square: to as u16,
+            from_square: from as u16,
+            promotion_piece,
+            flags,
+            weight: 0,
+        }
+    }
+
+    #[inline]
+    pub const fn is_capture(&self) -> bool {
+        self.flags & (Move::CAPTURE | Move::EN_PASSANT) != 0
+    }
+
+    //Long algebraic notation as used by UCI, e.g. e2e4 or e7e8q
+    pub fn from_uci(text: &str) -> Option<Self> {
+        if !text.is_ascii() || (text.len() != 4 && text.len() != 5) {
+            return None;
+        }
+        let from_square = parse_square(&text[0..2])?;
+        let to_square = parse_square(&text[2..4])?;
+        let promotion_piece = match text.get(4..5) {
+            None => PromotionPiece::None,
+            Some("n") => PromotionPiece::Knight,
+            Some("b") => PromotionPiece::Bishop,
+            Some("r") => PromotionPiece::Rook,
+            Some("q") => PromotionPiece::Queen,
+            Some(_) => return None,
+        };
+        Some(Move::new(
+            from_square,
+            to_square,
+            promotion_piece,
+            Move::QUIET,
+        ))
+    }
+
+    pub fn to_uci(self) -> String {
+        let mut res = string_square(self.from_square as usize);
+        res.push_str(&string_square(self.to_square as usize));
+        match self.promotion_piece {
+            PromotionPiece::None => (),
+            PromotionPiece::Knight => res.push('n'),
+            PromotionPiece::Bishop => res.push('b'),
+            PromotionPiece::Rook => res.push('r'),
+            PromotionPiece::Queen => res.push('q'),
+        }
+        res
+    }
+}
+
+//No position has more legal moves than this
+pub const MAX_MOVES: usize = 256;
+
+//Moves kept on the stack, so generating them never allocates
 #[derive(Clone, Copy)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub const fn new() -> Self {
+        MoveList {
+            moves: [Move::new(0, 0, PromotionPiece::None, Move::QUIET); MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, m: Move) {
+        self.moves[self.len] = m;
+        self.len += 1;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        MoveList::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
+}
+
+//Flags are derived from the position the move is played in, so two moves are the same move when they agree on
+//squares and promotion.
+impl PartialEq for Move {
+    fn eq(&self, other: &Self) -> bool {
+        (self.from_square == other.from_square)
+            & (self.to_square == other.to_square)
+            & (self.promotion_piece == other.promotion_piece)
+    }
+}
+
+impl Eq for Move {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PromotionPiece {
     None,
     Knight,
@@ -109,14 +254,51 @@ pub enum PromotionPiece {
 }
 
 impl PromotionPiece {
-    fn from_num(piece: u8) -> Self {
+    //Numbering used by the promotion bits of Polyglot book moves
+    pub fn from_num(piece: u8) -> Option<Self> {
         match piece {
-            0 => Self::None,
-            1 => Self::Knight,
-            2 => Self::Bishop,
-            3 => Self::Rook,
-            4 => Self::Queen,
-            _ => panic!("Invalid piece"),
+            0 => Some(Self::None),
+            1 => Some(Self::Knight),
+            2 => Some(Self::Bishop),
+            3 => Some(Self::Rook),
+            4 => Some(Self::Queen),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Board, Move, PromotionPiece};
+
+    fn play(fen: &str, text: &str) -> String {
+        let board = Board::from_fen(fen).unwrap();
+        board.make_move(Move::from_uci(text).unwrap()).to_fen()
+    }
+
+    #[test]
+    fn underpromotion() {
+        let fen = "8/1P6/8/8/8/8/6k1/K7 w - - 0 1";
+        assert_eq!(play(fen, "b7b8n"), "1N6/8/8/8/8/8/6k1/K7 b - - 0 1");
+        assert_eq!(play(fen, "b7b8b"), "1B6/8/8/8/8/8/6k1/K7 b - - 0 1");
+        assert_eq!(play(fen, "b7b8r"), "1R6/8/8/8/8/8/6k1/K7 b - - 0 1");
+        assert_eq!(play(fen, "b7b8q"), "1Q6/8/8/8/8/8/6k1/K7 b - - 0 1");
+        let fen = "8/8/8/8/8/K7/6p1/k4R2 b - - 0 1";
+        assert_eq!(play(fen, "g2f1n"), "8/8/8/8/8/K7/8/k4n2 w - - 0 2");
+    }
+
+    #[test]
+    fn uci_notation() {
+        for text in ["e2e4", "a1h8", "h7h8q", "b2b1n", "e1g1", "a8a7"] {
+            assert_eq!(Move::from_uci(text).unwrap().to_uci(), text);
+        }
+        let promotion = Move::from_uci("a7a8r").unwrap();
+        assert_eq!((promotion.from_square, promotion.to_square), (48, 56));
+        assert_eq!(promotion.promotion_piece, PromotionPiece::Rook);
+        for text in [
+            "", "e2", "e2e", "e2e4qq", "i2e4", "e9e4", "e2e4k", "E2E4", "e2-e4", "é2e4",
+        ] {
+            assert!(Move::from_uci(text).is_none(), "{}", text);
         }
     }
 }
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::{
+    board_move::{Move, PromotionPiece},
+    opening::OpeningBook,
+    pgn::{GameResult, PgnGame},
+    Board,
+};
+
+//Results of the games a move was played in, from the side that played it
+#[derive(Clone, Copy, Default)]
+struct Tally {
+    wins: u64,
+    draws: u64,
+    losses: u64,
+}
+
+//Collects moves from games and writes them out as a Polyglot book
+pub struct BookBuilder {
+    entries: HashMap<(u64, u16), Tally>,
+    //Half moves of each game that go into the book
+    pub max_ply: usize,
+    pub win_weight: u64,
+    pub draw_weight: u64,
+    pub loss_weight: u64,
+}
+
+impl BookBuilder {
+    pub fn new() -> Self {
+        BookBuilder {
+            entries: HashMap::new(),
+            max_ply: 30,
+            win_weight: 2,
+            draw_weight: 1,
+            loss_weight: 0,
+        }
+    }
+
+    //Adds the moves of a finished game. Returns false when part of the game couldn't be replayed, the moves before
+    //the bad one are still added.
+    pub fn add_game(&mut self, game: &PgnGame) -> bool {
+        let result = match game.result {
+            Some(result) => result,
+            None => return false,
+        };
+        let (moves, complete) = game.replay();
+        for (board, m) in moves.into_iter().take(self.max_ply) {
+            let tally = self
+                .entries
+                .entry((board.zobrist(), board.encode_polyglot(m)))
+                .or_default();
+            match (result, board.white_to_play) {
+                (GameResult::Draw, _) => tally.draws += 1,
+                (GameResult::WhiteWin, true) | (GameResult::BlackWin, false) => tally.wins += 1,
+                _ => tally.losses += 1,
+            }
+        }
+        complete
+    }
+
+    //Polyglot records sorted by key, the most played move of a position first
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut positions: HashMap<u64, Vec<(u16, u64)>> = HashMap::new();
+        for ((key, book_move), tally) in &self.entries {
+            let weight = tally.wins * self.win_weight
+                + tally.draws * self.draw_weight
+                + tally.losses * self.loss_weight;
+            positions
+                .entry(*key)
+                .or_default()
+                .push((*book_move, weight));
+        }
+        write_positions(positions)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+}
+
+impl Default for BookBuilder {
+    fn default() -> Self {
+        BookBuilder::new()
+    }
+}
+
+//One 16 byte entry of a Polyglot file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookRecord {
+    pub key: u64,
+    pub book_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookRecord {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        BookRecord {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            book_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; 16] {
+        let mut res = [0; 16];
+        res[0..8].copy_from_slice(&self.key.to_be_bytes());
+        res[8..10].copy_from_slice(&self.book_move.to_be_bytes());
+        res[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        res[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        res
+    }
+}
+
+//How the weights of a move found in several books are combined
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeWeights {
+    Sum,
+    Max,
+    //A position only takes its moves from the first book that has it
+    First,
+}
+
+impl MergeWeights {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "sum" => Some(Self::Sum),
+            "max" => Some(Self::Max),
+            "first" => Some(Self::First),
+            _ => None,
+        }
+    }
+}
+
+//Combines several books into the bytes of one, the same move in two books is the same stored move
+pub fn merge_books(books: &[OpeningBook], merge: MergeWeights) -> Vec<u8> {
+    let mut positions: HashMap<u64, Vec<(u16, u64)>> = HashMap::new();
+    //Which book each position came from, only kept when the first book to have a position owns it
+    let mut owner: HashMap<u64, usize> = HashMap::new();
+    for (index, book) in books.iter().enumerate() {
+        for record in book.records() {
+            if merge == MergeWeights::First && *owner.entry(record.key).or_insert(index) != index {
+                continue;
+            }
+            let moves = positions.entry(record.key).or_default();
+            let weight = record.weight as u64;
+            match moves.iter_mut().find(|x| x.0 == record.book_move) {
+                Some(existing) => match merge {
+                    MergeWeights::Sum | MergeWeights::First => existing.1 += weight,
+                    MergeWeights::Max => existing.1 = existing.1.max(weight),
+                },
+                None => moves.push((record.book_move, weight)),
+            }
+        }
+    }
+    write_positions(positions)
+}
+
+//Records for (move, weight) lists by key, the heaviest move of a position first. Moves with no weight are left out
+//and the weights of a position are scaled down together if any of them don't fit in 16 bits.
+fn write_positions(positions: HashMap<u64, Vec<(u16, u64)>>) -> Vec<u8> {
+    let mut records = Vec::new();
+    for (key, mut moves) in positions {
+        moves.retain(|x| x.1 > 0);
+        let heaviest = moves.iter().map(|x| x.1).max().unwrap_or(0);
+        let scale = heaviest.div_ceil(u16::MAX as u64).max(1);
+        moves.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (book_move, weight) in moves {
+            records.push(BookRecord {
+                key,
+                book_move,
+                weight: (weight / scale).max(1) as u16,
+                learn: 0,
+            });
+        }
+    }
+    write_records(&mut records)
+}
+
+//Sorts by key, keeping the order the moves of a position were given in, and lays the records out back to back
+pub fn write_records(records: &mut [BookRecord]) -> Vec<u8> {
+    records.sort_by_key(|x| x.key);
+    records.iter().flat_map(|x| x.to_bytes()).collect()
+}
+
+impl Board {
+    //The reverse of decode_polyglot, castling is written as the king taking its own rook
+    pub fn encode_polyglot(&self, m: Move) -> u16 {
+        let to_square = if m.flags & Move::CASTLE != 0 {
+            match m.to_square {
+                6 => 7,
+                2 => 0,
+                62 => 63,
+                58 => 56,
+                square => square,
+            }
+        } else {
+            m.to_square
+        };
+        let promotion = match m.promotion_piece {
+            PromotionPiece::None => 0,
+            PromotionPiece::Knight => 1,
+            PromotionPiece::Bishop => 2,
+            PromotionPiece::Rook => 3,
+            PromotionPiece::Queen => 4,
+        };
+        to_square | (m.from_square << 6) | (promotion << 12)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{opening::OpeningBook, pgn::parse_pgn};
+    use super::{merge_books, write_records, Board, BookBuilder, BookRecord, MergeWeights, Move};
+
+    const GAMES: &str = r#"[Result "1-0"]
+1. e4 e5 2. Nf3 Nc6 3. Bc4 Nf6 4. O-O 1-0
+
+[Result "0-1"]
+1. e4 c5 2. Nf3 d6 0-1
+
+[Result "1/2-1/2"]
+1. d4 d5 1/2-1/2
+
+[Result "1-0"]
+1. e4 e5 2. Nf3 Nf6 1-0
+"#;
+
+    #[test]
+    fn record_layout() {
+        let bytes = super::BookRecord {
+            key: 0x0102030405060708,
+            book_move: 0x090a,
+            weight: 0x0b0c,
+            learn: 0x0d0e0f10,
+        }
+        .to_bytes();
+        assert_eq!(
+            bytes,
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]
+        );
+    }
+
+    #[test]
+    fn builds_a_readable_book() {
+        let mut builder = BookBuilder::new();
+        for game in parse_pgn(GAMES) {
+            assert!(builder.add_game(&game));
+        }
+        let bytes = builder.to_bytes();
+        assert_eq!(bytes.len() % 16, 0);
+        let keys: Vec<u64> = bytes
+            .chunks_exact(16)
+            .map(|x| u64::from_be_bytes(x[0..8].try_into().unwrap()))
+            .collect();
+        assert!(keys.windows(2).all(|x| x[0] <= x[1]));
+
+        let book = OpeningBook::new(bytes).unwrap();
+        let start = Board::default();
+        let mut moves: Vec<(String, u16)> = book
+            .entries(start.zobrist())
+            .iter()
+            .map(|m| (start.decode_polyglot(*m).unwrap().to_uci(), m.weight))
+            .collect();
+        moves.sort();
+        //Two wins and a loss for e4, a draw for d4
+        assert_eq!(moves, [("d2d4".to_string(), 1), ("e2e4".to_string(), 4)]);
+
+        //Black lost both games with e5, so it never makes it into the book
+        let after_e4 = start.make_move(Move::from_uci("e2e4").unwrap());
+        let replies: Vec<String> = book
+            .entries(after_e4.zobrist())
+            .iter()
+            .map(|m| after_e4.decode_polyglot(*m).unwrap().to_uci())
+            .collect();
+        assert_eq!(replies, ["c7c5"]);
+
+        //Castling comes back out as the king's two square step
+        let castled = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        let board = Board::from_fen(castled).unwrap();
+        let castle = board.decode_polyglot(book.entries(board.zobrist())[0]);
+        assert_eq!(castle.unwrap().to_uci(), "e1g1");
+    }
+
+    fn book_with(entries: &[(u64, u16, u16)]) -> OpeningBook {
+        let mut records: Vec<BookRecord> = entries
+            .iter()
+            .map(|(key, book_move, weight)| BookRecord {
+                key: *key,
+                book_move: *book_move,
+                weight: *weight,
+                learn: 0,
+            })
+            .collect();
+        OpeningBook::new(write_records(&mut records)).unwrap()
+    }
+
+    fn weights(bytes: Vec<u8>) -> Vec<(u64, u16, u16)> {
+        bytes
+            .chunks_exact(16)
+            .map(BookRecord::from_bytes)
+            .map(|x| (x.key, x.book_move, x.weight))
+            .collect()
+    }
+
+    #[test]
+    fn merges_books() {
+        let first = book_with(&[(1, 10, 5), (1, 11, 2), (2, 20, 1)]);
+        let second = book_with(&[(1, 10, 3), (1, 12, 9), (3, 30, 60000)]);
+        let third = book_with(&[(3, 30, 60000), (3, 31, 2)]);
+        let books = [first, second, third];
+
+        assert_eq!(
+            weights(merge_books(&books[..2], MergeWeights::Sum)),
+            [
+                (1, 12, 9),
+                (1, 10, 8),
+                (1, 11, 2),
+                (2, 20, 1),
+                (3, 30, 60000)
+            ]
+        );
+        assert_eq!(
+            weights(merge_books(&books[..2], MergeWeights::Max)),
+            [
+                (1, 12, 9),
+                (1, 10, 5),
+                (1, 11, 2),
+                (2, 20, 1),
+                (3, 30, 60000)
+            ]
+        );
+        assert_eq!(
+            weights(merge_books(&books[..2], MergeWeights::First)),
+            [(1, 10, 5), (1, 11, 2), (2, 20, 1), (3, 30, 60000)]
+        );
+        //Weights that overflow are scaled down with the rest of the position
+        assert_eq!(
+            weights(merge_books(&books[1..], MergeWeights::Sum)),
+            [(1, 12, 9), (1, 10, 3), (3, 30, 60000), (3, 31, 1)]
+        );
+    }
+}
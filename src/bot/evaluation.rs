@@ -0,0 +1,440 @@
+use rand::Rng;
+
+use super::{pawns::PawnTable, utils::BitBoardIter, BitBoard, Board};
+
+//Game phase each piece is worth, 24 with every piece on the board
+const KNIGHT_PHASE: i32 = 1;
+const BISHOP_PHASE: i32 = 1;
+const ROOK_PHASE: i32 = 2;
+const QUEEN_PHASE: i32 = 4;
+const MAX_PHASE: i32 = 24;
+
+//Plain piece values for the material evaluators
+const PAWN_VALUE: i32 = 100;
+const KNIGHT_VALUE: i32 = 300;
+const BISHOP_VALUE: i32 = 300;
+const ROOK_VALUE: i32 = 500;
+const QUEEN_VALUE: i32 = 900;
+//How far the weak evaluator can be off either way by default
+const WEAK_NOISE: i32 = 100;
+
+//Pawn, knight, bishop, rook, queen, king
+const MIDDLEGAME_VALUE: [i32; 6] = [82, 337, 365, 477, 1025, 0];
+const ENDGAME_VALUE: [i32; 6] = [94, 281, 297, 512, 936, 0];
+
+impl Board {
+    //Material, piece placement, pawn structure and king safety in centipawns from white's point of view. Middlegame
+    //and endgame scores are blended by how much material is left, so the king hides early and walks up once the
+    //queens are gone.
+    pub fn evaluate(&self) -> i32 {
+        self.blend(self.pawn_structure())
+    }
+
+    //The same as evaluate with the pawn structure looked up in the table when it has been seen before
+    pub fn evaluate_cached(&self, pawn_table: &mut PawnTable) -> i32 {
+        self.blend(self.cached_pawn_structure(pawn_table))
+    }
+
+    fn blend(&self, pawns: (i32, i32)) -> i32 {
+        let (white_middlegame, white_endgame) = self.piece_square_score(true);
+        let (black_middlegame, black_endgame) = self.piece_square_score(false);
+        let middlegame = white_middlegame - black_middlegame + pawns.0 + self.king_safety();
+        let endgame = white_endgame - black_endgame + pawns.1;
+        let phase = self.phase();
+        (middlegame * phase + endgame * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+
+    //MAX_PHASE at the start of the game down to 0 with only pawns and kings left
+    pub fn phase(&self) -> i32 {
+        let phase = (self.white_knights | self.black_knights).count_ones() as i32 * KNIGHT_PHASE
+            + (self.white_bishops | self.black_bishops).count_ones() as i32 * BISHOP_PHASE
+            + (self.white_rooks | self.black_rooks).count_ones() as i32 * ROOK_PHASE
+            + (self.white_queens | self.black_queens).count_ones() as i32 * QUEEN_PHASE;
+        //Promotions can take it past the starting material
+        phase.min(MAX_PHASE)
+    }
+
+    fn piece_square_score(&self, white: bool) -> (i32, i32) {
+        let pieces: [BitBoard; 6] = if white {
+            [
+                self.white_pawns,
+                self.white_knights,
+                self.white_bishops,
+                self.white_rooks,
+                self.white_queens,
+                self.white_kings,
+            ]
+        } else {
+            [
+                self.black_pawns,
+                self.black_knights,
+                self.black_bishops,
+                self.black_rooks,
+                self.black_queens,
+                self.black_kings,
+            ]
+        };
+        let mut middlegame = 0;
+        let mut endgame = 0;
+        for (kind, bitboard) in pieces.into_iter().enumerate() {
+            for square in BitBoardIter(bitboard) {
+                //Tables are written from white's side with a8 first, black reads them mirrored
+                let index = if white { square ^ 56 } else { square };
+                middlegame += MIDDLEGAME_VALUE[kind] + MIDDLEGAME_TABLES[kind][index];
+                endgame += ENDGAME_VALUE[kind] + ENDGAME_TABLES[kind][index];
+            }
+        }
+        (middlegame, endgame)
+    }
+
+    //Piece count difference in centipawns from white's point of view
+    pub fn material(&self) -> i32 {
+        let count = |white: BitBoard, black: BitBoard| {
+            white.count_ones() as i32 - black.count_ones() as i32
+        };
+        count(self.white_pawns, self.black_pawns) * PAWN_VALUE
+            + count(self.white_knights, self.black_knights) * KNIGHT_VALUE
+            + count(self.white_bishops, self.black_bishops) * BISHOP_VALUE
+            + count(self.white_rooks, self.black_rooks) * ROOK_VALUE
+            + count(self.white_queens, self.black_queens) * QUEEN_VALUE
+    }
+}
+
+//Static evaluation used at the leaves of the search. Scores are centipawns from white's point of view. Evaluators
+//take themselves mutably so they can keep caches between positions.
+pub trait Evaluator {
+    fn evaluate(&mut self, board: &Board) -> i32;
+}
+
+impl<E: Evaluator + ?Sized> Evaluator for Box<E> {
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        (**self).evaluate(board)
+    }
+}
+
+//Tapered piece-square tables with pawn structure and king safety
+#[derive(Default)]
+pub struct StandardEvaluator {
+    pawn_table: PawnTable,
+}
+
+impl StandardEvaluator {
+    pub fn new() -> Self {
+        StandardEvaluator {
+            pawn_table: PawnTable::new(),
+        }
+    }
+}
+
+impl Evaluator for StandardEvaluator {
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        board.evaluate_cached(&mut self.pawn_table)
+    }
+}
+
+//Counts pieces and nothing else
+pub struct MaterialEvaluator;
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        board.material()
+    }
+}
+
+//Material with random noise on top, so the bot misjudges positions and gives beginners a chance
+pub struct WeakEvaluator {
+    pub noise: i32,
+}
+
+impl Default for WeakEvaluator {
+    fn default() -> Self {
+        WeakEvaluator { noise: WEAK_NOISE }
+    }
+}
+
+impl Evaluator for WeakEvaluator {
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        board.material() + rand::thread_rng().gen_range(-self.noise..=self.noise)
+    }
+}
+
+//The evaluators that can be picked by name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvaluatorKind {
+    Standard,
+    Material,
+    Weak,
+}
+
+impl EvaluatorKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "standard" => Some(Self::Standard),
+            "material" => Some(Self::Material),
+            "weak" => Some(Self::Weak),
+            _ => None,
+        }
+    }
+
+    pub fn build(self) -> Box<dyn Evaluator + Send> {
+        match self {
+            Self::Standard => Box::new(StandardEvaluator::new()),
+            Self::Material => Box::new(MaterialEvaluator),
+            Self::Weak => Box::new(WeakEvaluator::default()),
+        }
+    }
+}
+
+const MIDDLEGAME_TABLES: [[i32; 64]; 6] = [
+    MIDDLEGAME_PAWN,
+    MIDDLEGAME_KNIGHT,
+    MIDDLEGAME_BISHOP,
+    MIDDLEGAME_ROOK,
+    MIDDLEGAME_QUEEN,
+    MIDDLEGAME_KING,
+];
+
+const ENDGAME_TABLES: [[i32; 64]; 6] = [
+    ENDGAME_PAWN,
+    ENDGAME_KNIGHT,
+    ENDGAME_BISHOP,
+    ENDGAME_ROOK,
+    ENDGAME_QUEEN,
+    ENDGAME_KING,
+];
+
+#[rustfmt::skip]
+const MIDDLEGAME_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     98, 134,  61,  95,  68, 126,  34, -11,
+     -6,   7,  26,  31,  65,  56,  25, -20,
+    -14,  13,   6,  21,  23,  12,  17, -23,
+    -27,  -2,  -5,  12,  17,   6,  10, -25,
+    -26,  -4,  -4, -10,   3,   3,  33, -12,
+    -35,  -1, -20, -23, -15,  24,  38, -22,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const ENDGAME_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const MIDDLEGAME_KNIGHT: [i32; 64] = [
+    -167, -89, -34, -49,  61, -97, -15, -107,
+     -73, -41,  72,  36,  23,  62,   7,  -17,
+     -47,  60,  37,  65,  84, 129,  73,   44,
+      -9,  17,  19,  53,  37,  69,  18,   22,
+     -13,   4,  16,  13,  28,  19,  21,   -8,
+     -23,  -9,  12,  10,  19,  17,  25,  -16,
+     -29, -53, -12,  -3,  -1,  18, -14,  -19,
+    -105, -21, -58, -33, -17, -28, -19,  -23,
+];
+
+#[rustfmt::skip]
+const ENDGAME_KNIGHT: [i32; 64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,
+];
+
+#[rustfmt::skip]
+const MIDDLEGAME_BISHOP: [i32; 64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,
+];
+
+#[rustfmt::skip]
+const ENDGAME_BISHOP: [i32; 64] = [
+    -14, -21, -11,  -8,  -7,  -9, -17, -24,
+     -8,  -4,   7, -12,  -3, -13,  -4, -14,
+      2,  -8,   0,  -1,  -2,   6,   0,   4,
+     -3,   9,  12,   9,  14,  10,   3,   2,
+     -6,   3,  13,  19,   7,  10,  -3,  -9,
+    -12,  -3,   8,  10,  13,   3,  -7, -15,
+    -14, -18,  -7,  -1,   4,  -9, -15, -27,
+    -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+];
+
+#[rustfmt::skip]
+const MIDDLEGAME_ROOK: [i32; 64] = [
+     32,  42,  32,  51,  63,   9,  31,  43,
+     27,  32,  58,  62,  80,  67,  26,  44,
+     -5,  19,  26,  36,  17,  45,  61,  16,
+    -24, -11,   7,  26,  24,  35,  -8, -20,
+    -36, -26, -12,  -1,   9,  -7,   6, -23,
+    -45, -25, -16, -17,   3,   0,  -5, -33,
+    -44, -16, -20,  -9,  -1,  11,  -6, -71,
+    -19, -13,   1,  17,  16,   7, -37, -26,
+];
+
+#[rustfmt::skip]
+const ENDGAME_ROOK: [i32; 64] = [
+     13,  10,  18,  15,  12,  12,   8,   5,
+     11,  13,  13,  11,  -3,   3,   8,   3,
+      7,   7,   7,   5,   4,  -3,  -5,  -3,
+      4,   3,  13,   1,   2,   1,  -1,   2,
+      3,   5,   8,   4,  -5,  -6,  -8, -11,
+     -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+     -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+     -9,   2,   3,  -1,  -5, -13,   4, -20,
+];
+
+#[rustfmt::skip]
+const MIDDLEGAME_QUEEN: [i32; 64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,
+];
+
+#[rustfmt::skip]
+const ENDGAME_QUEEN: [i32; 64] = [
+     -9,  22,  22,  27,  27,  19,  10,  20,
+    -17,  20,  32,  41,  58,  25,  30,   0,
+    -20,   6,   9,  49,  47,  35,  19,   9,
+      3,  22,  24,  45,  57,  40,  57,  36,
+    -18,  28,  19,  47,  31,  34,  39,  23,
+    -16, -27,  15,   6,   9,  17,  10,   5,
+    -22, -23, -30, -16, -16, -23, -36, -32,
+    -33, -28, -22, -43,  -5, -32, -20, -41,
+];
+
+#[rustfmt::skip]
+const MIDDLEGAME_KING: [i32; 64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+     -9,  24,   2, -16, -20,   6,  22, -22,
+    -17, -20, -12, -27, -30, -25, -14, -36,
+    -49,  -1, -27, -39, -46, -44, -33, -51,
+    -14, -14, -22, -46, -44, -30, -15, -27,
+      1,   7,  -8, -64, -43, -16,   9,   8,
+    -15,  36,  12, -54,   8, -28,  24,  14,
+];
+
+#[rustfmt::skip]
+const ENDGAME_KING: [i32; 64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{Board, Evaluator, EvaluatorKind, WeakEvaluator, MAX_PHASE};
+
+    //The same position with the colours swapped and the board turned around
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |text: &str| -> String {
+            text.chars()
+                .map(|c| {
+                    if c.is_ascii_uppercase() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        c.to_ascii_uppercase()
+                    }
+                })
+                .collect()
+        };
+        let ranks: Vec<&str> = fields[0].split('/').rev().collect();
+        let turn = if fields[1] == "w" { "b" } else { "w" };
+        let en_passant = fields[3]
+            .replace('3', "x")
+            .replace('6', "3")
+            .replace('x', "6");
+        format!(
+            "{} {} {} {} {} {}",
+            swap_case(&ranks.join("/")),
+            turn,
+            swap_case(fields[2]),
+            en_passant,
+            fields[4],
+            fields[5]
+        )
+    }
+
+    #[test]
+    fn symmetric() {
+        assert_eq!(Board::default().evaluate(), 0);
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let mirrored = Board::from_fen(&mirror(fen)).unwrap();
+            assert_eq!(board.evaluate(), -mirrored.evaluate(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn phase() {
+        assert_eq!(Board::default().phase(), MAX_PHASE);
+        let endgame = Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(endgame.phase(), 4);
+        let pawns = Board::from_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap();
+        assert_eq!(pawns.phase(), 0);
+    }
+
+    #[test]
+    fn prefers_good_squares() {
+        let score = |fen: &str| Board::from_fen(fen).unwrap().evaluate();
+        //A centralised knight beats one on the rim
+        assert!(score("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1") > score("4k3/8/8/8/8/8/8/N3K3 w - - 0 1"));
+        //A castled king beats one left in the centre while the queens are on
+        assert!(
+            score("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w kq - 0 1")
+                > score("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQK2R w Kkq - 0 1")
+        );
+        //An advanced passed pawn is worth more in the endgame
+        assert!(
+            score("4k3/8/1P6/8/8/8/8/4K3 w - - 0 1") > score("4k3/8/8/8/8/8/1P6/4K3 w - - 0 1")
+        );
+    }
+
+    #[test]
+    fn evaluators() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/PPP5/RN2K3 w - - 0 1").unwrap();
+        assert_eq!(board.material(), 1100);
+        for kind in [
+            EvaluatorKind::Standard,
+            EvaluatorKind::Material,
+            EvaluatorKind::Weak,
+        ] {
+            assert!(kind.build().evaluate(&board) > 0, "{:?}", kind);
+        }
+        let mut weak = WeakEvaluator { noise: 50 };
+        for _ in 0..100 {
+            assert!((weak.evaluate(&board) - 1100).abs() <= 50);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use super::board_move::{Move, PromotionPiece};
+
+//History scores stay between plus and minus this, a cutoff moves a score part of the way towards the limit
+const MAX_HISTORY: i32 = 16384;
+//Puts the countermove ahead of every other quiet move
+const COUNTERMOVE_BONUS: i32 = 2 * MAX_HISTORY;
+
+//What the search learns about quiet moves from beta cutoffs, used to order the quiet moves of later nodes
+pub struct Heuristics {
+    //Two quiet moves that caused a cutoff at each ply, the latest first
+    killers: Vec<[Option<Move>; 2]>,
+    //Butterfly table by side, from square and to square
+    history: Box<[[[i32; 64]; 64]; 2]>,
+    //The quiet move that refuted each move, by the from and to square of the move refuted
+    countermoves: Box<[[Option<Move>; 64]; 64]>,
+}
+
+impl Heuristics {
+    pub fn new(max_ply: usize) -> Self {
+        Heuristics {
+            killers: vec![[None; 2]; max_ply + 1],
+            history: Box::new([[[0; 64]; 64]; 2]),
+            countermoves: Box::new([[None; 64]; 64]),
+        }
+    }
+
+    pub fn killers(&self, ply: usize) -> [Option<Move>; 2] {
+        self.killers[ply]
+    }
+
+    //Ordering score for a quiet move, previous is the move the other side just played
+    pub fn quiet_score(&self, white: bool, m: Move, previous: Option<Move>) -> i32 {
+        let score = self.history[white as usize][m.from_square as usize][m.to_square as usize];
+        if previous.is_some_and(|p| self.countermove(p) == Some(m)) {
+            score + COUNTERMOVE_BONUS
+        } else {
+            score
+        }
+    }
+
+    fn countermove(&self, previous: Move) -> Option<Move> {
+        self.countermoves[previous.from_square as usize][previous.to_square as usize]
+    }
+
+    //Rewards a quiet move that caused a beta cutoff and penalises the quiet moves searched before it
+    pub fn update(
+        &mut self,
+        ply: usize,
+        depth: usize,
+        white: bool,
+        cutoff: Move,
+        previous: Option<Move>,
+        tried: &[Move],
+    ) {
+        if self.killers[ply][0] != Some(cutoff) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(cutoff);
+        }
+        if let Some(previous) = previous {
+            self.countermoves[previous.from_square as usize][previous.to_square as usize] =
+                Some(cutoff);
+        }
+        let bonus = (depth * depth).min(MAX_HISTORY as usize) as i32;
+        self.add_history(white, cutoff, bonus);
+        for m in tried.iter().filter(|m| **m != cutoff) {
+            self.add_history(white, *m, -bonus);
+        }
+    }
+
+    fn add_history(&mut self, white: bool, m: Move, bonus: i32) {
+        let entry = &mut self.history[white as usize][m.from_square as usize][m.to_square as usize];
+        *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+    }
+}
+
+//Only quiet moves are remembered, captures are ordered by what they take
+pub fn is_quiet(m: Move) -> bool {
+    !m.is_capture() & (m.promotion_piece == PromotionPiece::None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Heuristics, Move, MAX_HISTORY};
+
+    fn uci(text: &str) -> Move {
+        Move::from_uci(text).unwrap()
+    }
+
+    #[test]
+    fn cutoffs_update_ordering() {
+        let mut heuristics = Heuristics::new(8);
+        let tried = [uci("a2a3"), uci("b1c3")];
+        heuristics.update(3, 4, true, uci("b1c3"), Some(uci("e7e5")), &tried);
+        heuristics.update(3, 2, true, uci("g1f3"), None, &[]);
+        assert_eq!(
+            heuristics.killers(3),
+            [Some(uci("g1f3")), Some(uci("b1c3"))]
+        );
+        assert_eq!(heuristics.killers(2), [None, None]);
+        //The move that failed first is pushed down, the cutoff move goes up for the side that played it
+        assert!(heuristics.quiet_score(true, uci("a2a3"), None) < 0);
+        assert_eq!(heuristics.quiet_score(true, uci("b1c3"), None), 16);
+        assert_eq!(heuristics.quiet_score(false, uci("b1c3"), None), 0);
+        //The countermove comes before any move with only history behind it
+        assert!(heuristics.quiet_score(true, uci("b1c3"), Some(uci("e7e5"))) > MAX_HISTORY);
+        //Repeated cutoffs never take a score past the limit
+        for _ in 0..1000 {
+            heuristics.update(0, 60, true, uci("g1f3"), None, &[]);
+        }
+        assert!(heuristics.quiet_score(true, uci("g1f3"), None) <= MAX_HISTORY);
+    }
+}
@@ -0,0 +1,62 @@
+//Zobrist keys of the positions played so far, used to spot repetitions
+#[derive(Clone)]
+pub struct History {
+    keys: Vec<u64>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History {
+            keys: Vec::with_capacity(256),
+        }
+    }
+
+    pub fn push(&mut self, key: u64) {
+        self.keys.push(key);
+    }
+
+    pub fn pop(&mut self) {
+        self.keys.pop();
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+
+    //Nothing before the last capture or pawn move can come back, so only the last halfmove_clock positions are checked
+    pub fn is_repetition(&self, key: u64, halfmove_clock: u16) -> bool {
+        self.keys
+            .iter()
+            .rev()
+            .take(halfmove_clock as usize)
+            .any(|x| *x == key)
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::History;
+
+    #[test]
+    fn repetitions() {
+        let mut history = History::new();
+        for key in [1, 2, 3, 4, 5] {
+            history.push(key);
+        }
+        //Key 2 was seen four half moves ago
+        assert!(history.is_repetition(2, 4));
+        assert!(history.is_repetition(5, 1));
+        //A capture or pawn move since then means it can't be the same position
+        assert!(!history.is_repetition(2, 3));
+        assert!(!history.is_repetition(5, 0));
+        assert!(!history.is_repetition(6, 100));
+        history.pop();
+        assert!(!history.is_repetition(5, 100));
+    }
+}
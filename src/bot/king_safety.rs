@@ -0,0 +1,165 @@
+use super::{
+    magic_bitboards::{bishop_moves, queen_moves, rook_moves},
+    pseudomoves::{KING_MOVES, KNIGHT_MOVES},
+    utils::BitBoardIter,
+    BitBoard, Board,
+};
+
+const FILE_A: BitBoard = 0x0101010101010101;
+const FIRST_RANK: BitBoard = 0xFF;
+
+//How much a piece that reaches the squares around the king counts towards the attack
+const KNIGHT_ATTACK: usize = 2;
+const BISHOP_ATTACK: usize = 2;
+const ROOK_ATTACK: usize = 3;
+const QUEEN_ATTACK: usize = 5;
+//Penalty by the summed weight of the attackers. It grows faster than the weights so pieces working together on the
+//king count for more than the same pieces spread over the board.
+const ATTACK_PENALTY: [i32; 16] = [
+    0, 0, 5, 10, 20, 30, 45, 60, 80, 100, 125, 150, 180, 210, 250, 300,
+];
+//For every square around the king the other side attacks
+const ATTACKED_SQUARE: i32 = -4;
+//For a pawn one and two ranks in front of the king, on its file or the files beside it
+const SHIELD: [i32; 2] = [12, 6];
+//Files beside the king with no pawn of its own on them, and ones with no pawns at all
+const SEMI_OPEN_FILE: i32 = -12;
+const OPEN_FILE: i32 = -20;
+
+impl Board {
+    //Middlegame centipawns from white's point of view. Only the middlegame score is affected, with the queens off the
+    //king wants to come out and help.
+    pub fn king_safety(&self) -> i32 {
+        self.king_score(true) - self.king_score(false)
+    }
+
+    fn king_score(&self, white: bool) -> i32 {
+        let (king, own_pawns, enemy_pawns) = if white {
+            (self.white_kings, self.white_pawns, self.black_pawns)
+        } else {
+            (self.black_kings, self.black_pawns, self.white_pawns)
+        };
+        if king == 0 {
+            return 0;
+        }
+        let square = king.trailing_zeros() as usize;
+        let zone = KING_MOVES[square] | king;
+        let mut score = 0;
+
+        let enemy_attacks = if white {
+            self.under_attack_by_black()
+        } else {
+            self.under_attack_by_white()
+        };
+        score += (zone & enemy_attacks).count_ones() as i32 * ATTACKED_SQUARE;
+
+        let (attackers, weight) = self.king_attackers(zone, !white);
+        //A single piece near the king is rarely a threat on its own
+        if attackers > 1 {
+            score -= ATTACK_PENALTY[weight.min(ATTACK_PENALTY.len() - 1)];
+        }
+
+        let file = square % 8;
+        let rank = square / 8;
+        let files = (file.saturating_sub(1)..=(file + 1).min(7))
+            .fold(0, |files, file| files | (FILE_A << file));
+        for (distance, bonus) in SHIELD.into_iter().enumerate() {
+            let shield_rank = if white {
+                rank + distance + 1
+            } else {
+                rank.wrapping_sub(distance + 1)
+            };
+            if shield_rank < 8 {
+                let shield = files & (FIRST_RANK << (shield_rank * 8));
+                score += (own_pawns & shield).count_ones() as i32 * bonus;
+            }
+        }
+
+        for file in BitBoardIter(files & FIRST_RANK) {
+            let file = FILE_A << file;
+            if own_pawns & file == 0 {
+                score += if enemy_pawns & file == 0 {
+                    OPEN_FILE
+                } else {
+                    SEMI_OPEN_FILE
+                };
+            }
+        }
+        score
+    }
+
+    //Number of pieces of one side that reach the zone and their summed weight
+    fn king_attackers(&self, zone: BitBoard, white: bool) -> (usize, usize) {
+        let (knights, bishops, rooks, queens) = if white {
+            (
+                self.white_knights,
+                self.white_bishops,
+                self.white_rooks,
+                self.white_queens,
+            )
+        } else {
+            (
+                self.black_knights,
+                self.black_bishops,
+                self.black_rooks,
+                self.black_queens,
+            )
+        };
+        let empty = !self.occupied;
+        let mut attackers = 0;
+        let mut weight = 0;
+        let mut count = |attacks: BitBoard, piece_weight: usize| {
+            if attacks & zone != 0 {
+                attackers += 1;
+                weight += piece_weight;
+            }
+        };
+        for i in BitBoardIter(knights) {
+            count(KNIGHT_MOVES[i], KNIGHT_ATTACK);
+        }
+        for i in BitBoardIter(bishops) {
+            count(bishop_moves(i, empty), BISHOP_ATTACK);
+        }
+        for i in BitBoardIter(rooks) {
+            count(rook_moves(i, empty), ROOK_ATTACK);
+        }
+        for i in BitBoardIter(queens) {
+            count(queen_moves(i, empty), QUEEN_ATTACK);
+        }
+        (attackers, weight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Board;
+
+    fn safety(fen: &str) -> i32 {
+        Board::from_fen(fen).unwrap().king_safety()
+    }
+
+    #[test]
+    fn shelter() {
+        //Pawns in front of the castled king are better than pawns that have been pushed or are gone
+        let castled = "6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1";
+        assert_eq!(safety(castled), 0);
+        assert!(safety("6k1/5ppp/8/8/8/6PP/5P2/6K1 w - - 0 1") < 0);
+        assert!(safety("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1") < 0);
+        //A file with no pawns at all is worse than one that still has an enemy pawn on it
+        let white_king = |fen: &str| Board::from_fen(fen).unwrap().king_score(true);
+        assert!(
+            white_king("6k1/5p1p/8/8/8/8/5P1P/6K1 w - - 0 1")
+                < white_king("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1")
+        );
+    }
+
+    #[test]
+    fn attackers() {
+        //The queen alone doesn't count as an attack, with a knight beside it the king is in trouble
+        let queen = safety("6k1/5ppp/8/8/8/5q2/5PPP/6K1 w - - 0 1");
+        let queen_and_knight = safety("6k1/5ppp/8/8/7n/5q2/5PPP/6K1 w - - 0 1");
+        assert!(queen_and_knight < queen - 20);
+        //A knight far from the king changes nothing
+        assert_eq!(safety("n5k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1"), 0);
+    }
+}
@@ -1,296 +1,485 @@
-use super::pseudomoves;
-use super::pseudomoves::rook_moves;
+use std::sync::OnceLock;
+
+use super::pseudomoves::{ray_bishop_moves, ray_bishop_xray, ray_rook_moves, ray_rook_xray, RAYS};
 use super::BitBoard;
 
-const ROOK_LOOKUP: [[BitBoard; 16384]; 64] = {
-    let mut res: [[BitBoard; 16384]; 64] = [[0; 16384]; 64];
-    let mut i: i32 = 0;
+const RANK_1: BitBoard = 0x00000000000000FF;
+const RANK_8: BitBoard = 0xFF00000000000000;
+const FILE_A: BitBoard = 0x0101010101010101;
+const FILE_H: BitBoard = 0x8080808080808080;
+
+//Attacks of a slider for every arrangement of the pieces that can block it, indexed by multiplying the blockers by
+//a magic number that packs them into the top bits. Squares on the edge of the board never block anything further
+//so they are left out of the mask.
+#[derive(Clone, Copy)]
+struct Magic {
+    mask: BitBoard,
+    magic: BitBoard,
+    shift: u32,
+    offset: usize,
+}
+
+const ROOK: [Magic; 64] = magics(ROOK_MASKS, ROOK_MAGICS);
+const BISHOP: [Magic; 64] = magics(BISHOP_MASKS, BISHOP_MAGICS);
+const ROOK_XRAY: [Magic; 64] = magics(ROOK_MASKS, ROOK_XRAY_MAGICS);
+const BISHOP_XRAY: [Magic; 64] = magics(BISHOP_MASKS, BISHOP_XRAY_MAGICS);
+
+//The attack tables of every slider, built once and shared by everything that generates moves
+struct SliderTables {
+    rook: Vec<BitBoard>,
+    bishop: Vec<BitBoard>,
+    rook_xray: Vec<BitBoard>,
+    bishop_xray: Vec<BitBoard>,
+}
+
+static TABLES: OnceLock<SliderTables> = OnceLock::new();
+
+//Builds the tables unless that has already happened. The first lookup builds them anyway, calling this at startup
+//keeps the work out of the first search.
+pub fn init_tables() {
+    tables();
+}
+
+pub fn tables_ready() -> bool {
+    TABLES.get().is_some()
+}
+
+#[inline]
+fn tables() -> &'static SliderTables {
+    TABLES.get_or_init(|| SliderTables {
+        rook: fill_table(&ROOK, ray_rook_moves),
+        bishop: fill_table(&BISHOP, ray_bishop_moves),
+        rook_xray: fill_table(&ROOK_XRAY, ray_rook_xray),
+        bishop_xray: fill_table(&BISHOP_XRAY, ray_bishop_xray),
+    })
+}
+
+#[inline]
+pub fn rook_moves(square: usize, empty: BitBoard) -> BitBoard {
+    lookup(&ROOK[square], &tables().rook, !empty)
+}
+
+#[inline]
+pub fn bishop_moves(square: usize, empty: BitBoard) -> BitBoard {
+    lookup(&BISHOP[square], &tables().bishop, !empty)
+}
+
+#[inline]
+pub fn queen_moves(square: usize, empty: BitBoard) -> BitBoard {
+    rook_moves(square, empty) | bishop_moves(square, empty)
+}
+
+//Attacks that carry on through the first piece in the way up to the second, for finding pins
+#[inline]
+pub fn rook_xray(square: usize, empty: BitBoard) -> BitBoard {
+    lookup(&ROOK_XRAY[square], &tables().rook_xray, !empty)
+}
+
+#[inline]
+pub fn bishop_xray(square: usize, empty: BitBoard) -> BitBoard {
+    lookup(&BISHOP_XRAY[square], &tables().bishop_xray, !empty)
+}
+
+#[inline]
+pub fn queen_xray(square: usize, empty: BitBoard) -> BitBoard {
+    rook_xray(square, empty) | bishop_xray(square, empty)
+}
+
+#[inline]
+fn lookup(magic: &Magic, table: &[BitBoard], occupied: BitBoard) -> BitBoard {
+    let index = ((occupied & magic.mask).wrapping_mul(magic.magic) >> magic.shift) as usize;
+    table[magic.offset + index]
+}
+
+const ROOK_MASKS: [BitBoard; 64] = {
+    let mut res = [0; 64];
+    let mut i = 0;
+    while i < 64 {
+        res[i] = (RAYS[0][i] & !RANK_8)
+            | (RAYS[2][i] & !FILE_H)
+            | (RAYS[4][i] & !RANK_1)
+            | (RAYS[6][i] & !FILE_A);
+        i += 1;
+    }
+    res
+};
+
+const BISHOP_MASKS: [BitBoard; 64] = {
+    let mut res = [0; 64];
+    let mut i = 0;
     while i < 64 {
-        res[i as usize] = single_rook_lookup(i);
+        res[i] = (RAYS[1][i] | RAYS[3][i] | RAYS[5][i] | RAYS[7][i])
+            & !(RANK_1 | RANK_8 | FILE_A | FILE_H);
         i += 1;
     }
     res
 };
 
-const fn single_rook_lookup(square: i32) -> [BitBoard; 16384] {
-    let res: [BitBoard; 16384] = [0; 16384];
+//Each square gets one entry for every subset of its mask, one after the other in a shared table
+const fn magics(masks: [BitBoard; 64], numbers: [BitBoard; 64]) -> [Magic; 64] {
+    let mut res = [Magic {
+        mask: 0,
+        magic: 0,
+        shift: 0,
+        offset: 0,
+    }; 64];
+    let mut offset = 0;
     let mut i = 0;
-    let mut squares_bitboard: BitBoard = rook_moves(i, !0);
-    let mut squares = [0; 14];
-    let mut count = 0;
-    while squares_bitboard != 0 {
-        let res = squares_bitboard.trailing_zeros() as usize;
-        squares_bitboard &= !(1 << squares_bitboard.trailing_zeros());
-        squares[count as usize] = res;
-        count += 1;
+    while i < 64 {
+        let bits = masks[i].count_ones();
+        res[i] = Magic {
+            mask: masks[i],
+            magic: numbers[i],
+            shift: 64 - bits,
+            offset,
+        };
+        offset += 1 << bits;
+        i += 1;
     }
     res
 }
 
-const ROOK_SEED: [BitBoard; 64] = [
-    0x0050020428000230,
-    0x23000C80400004A0,
-    0x8030010086000090,
-    0x0028002022A00028,
-    0x8050028010500041,
-    0x8030018800C80030,
-    0x00300060003000C0,
-    0x8050040850000960,
-    0x580028010114000A,
-    0x10000C0086000300,
-    0x1800080402010008,
-    0x0C120002848A0001,
-    0x0080300180009021,
-    0x00801800C0180060,
-    0x1580680068080011,
-    0x8300180030640018,
-    0x00300018010C0003,
-    0x0706000C00830004,
-    0x1941000804020008,
-    0x5004002002002004,
-    0x640E004002004004,
-    0x220A001000410010,
-    0x8288004001000080,
-    0x00001200003C0009,
-    0x4140200010080010,
-    0x8010040008020008,
-    0x1042010008040008,
-    0x9020020020040021,
-    0x0040010020020020,
-    0x00C0008020092020,
-    0x4260400040008001,
-    0x202FC00040000080,
-    0x0800091800300030,
-    0x3004000800100011,
-    0x0200400880410010,
-    0x80E0200400200200,
-    0xB480200100200200,
-    0x94A0200080200100,
-    0x3000200480200040,
-    0x0054802000200040,
-    0x0400020A14002800,
-    0x0300030086000C00,
-    0x2400400840804200,
-    0x0300002049801800,
-    0x2010009001803003,
-    0x1200004001004002,
-    0x442000100800A804,
-    0x5108000D90005802,
-    0x000100022C008058,
-    0x4C00068880110020,
-    0x1080030040488018,
-    0xA980200200040020,
-    0x20104000310D8040,
-    0x0440500010208050,
-    0x0440000810580050,
-    0x2107000002280028,
-    0x080004802010411A,
-    0x3300010180086C41,
-    0x08200000C05D0501,
-    0x08904000081003E2,
-    0x020200004501428A,
-    0xA000000403700802,
-    0x040C000448008402,
-    0x2042000829028842,
-];
+fn fill_table(magics: &[Magic; 64], attacks: fn(usize, BitBoard) -> BitBoard) -> Vec<BitBoard> {
+    let size = magics[63].offset + (1 << (64 - magics[63].shift));
+    let mut res = vec![0; size];
+    for (square, magic) in magics.iter().enumerate() {
+        //Walks through every subset of the mask, starting and ending with the empty one
+        let mut blockers: BitBoard = 0;
+        loop {
+            let index = (blockers.wrapping_mul(magic.magic) >> magic.shift) as usize;
+            res[magic.offset + index] = attacks(square, !blockers);
+            blockers = blockers.wrapping_sub(magic.mask) & magic.mask;
+            if blockers == 0 {
+                break;
+            }
+        }
+    }
+    res
+}
 
-const BISHOP_SEED: [BitBoard; 64] = [
-    0x1C10004100408005,
-    0x000060C012000C04,
-    0x0000304829001002,
-    0x80005014028802C0,
-    0x10003C0503004801,
-    0x4006008008000011,
-    0x4010054040014100,
-    0x0000201002010040,
-    0x100501002A900008,
-    0x2300180200C00805,
-    0x101000A0A0140281,
-    0x2508A00210010C10,
-    0x800C003C01400814,
-    0x000188007C002000,
-    0x4500041008010010,
-    0x000000210240C048,
-    0x0020001004010040,
-    0x0140080020202002,
-    0x0000088010102008,
-    0x4000085080200448,
-    0x8020200180500029,
-    0x04500200400802C0,
-    0x0A04880100020100,
-    0x2002401040020080,
-    0x0088000D0701C080,
-    0x0243001801818018,
-    0x8080008980100400,
-    0x00420020280007A0,
-    0x0201C00820041001,
-    0x4100A02800410500,
-    0x0400802000802010,
-    0x00081800C4084042,
-    0x0100008017008028,
-    0x2400002843402014,
-    0x00100200100200A4,
-    0x0060004010010010,
-    0x6000802002008008,
-    0x0341829024000608,
-    0x0320601803001094,
-    0x0600483001803018,
-    0x2220004051230010,
-    0x0000001480410008,
-    0x0220A02010002800,
-    0x000C800840300018,
-    0x40810300C00C0012,
-    0x0248040080020020,
-    0x0110020080200100,
-    0x4000104003081401,
-    0x00000020150080A4,
-    0x0A00200020800908,
-    0xE44B04000C001020,
-    0x000A080000800EC1,
-    0x0003200020000BE0,
-    0x0420001280100400,
-    0x0000008080280560,
-    0x20000100400400B0,
-    0x9010000C000B0013,
-    0x020880020005800C,
-    0x0061002010410028,
-    0x6004100090080060,
-    0x000803000045000C,
-    0x4808000141020014,
-    0x024C004041004001,
-    0x8100401840102001,
+//Found by trying sparse random numbers until one sent every subset of the mask to a slot with the right attacks
+const ROOK_MAGICS: [BitBoard; 64] = [
+    0x2080002080400010,
+    0x00C0002001401000,
+    0x2100110008402002,
+    0x0880080081041000,
+    0x0200020020041008,
+    0x2300040008010012,
+    0x0C00283004008201,
+    0x0180010000407A80,
+    0x0168800080400020,
+    0x0010400040201000,
+    0x1001002001001048,
+    0x1001002408100100,
+    0x0801000408010012,
+    0x4001000209000400,
+    0x08A20004C8020001,
+    0x2002801145002280,
+    0x0080860021004200,
+    0x001000C009402002,
+    0x00B0002004002800,
+    0x100A808010020800,
+    0x8101010008000410,
+    0x0244008002000480,
+    0x0000040010810208,
+    0x2000020000448534,
+    0x4104400480008033,
+    0x0000810100204000,
+    0x0440430900200010,
+    0x4600240900100100,
+    0x0060080080040080,
+    0x0001000300080400,
+    0x0004084400011002,
+    0x0023040200008041,
+    0x0580050043002080,
+    0x0400804002802008,
+    0x0001002001004010,
+    0x1000200901001000,
+    0x4410800801800C00,
+    0xA012003806001004,
+    0x0020100104008802,
+    0x0004808402000041,
+    0x0010400170898000,
+    0x0080500020004004,
+    0x1040408012020020,
+    0x8010040008004040,
+    0x2001080100110004,
+    0x0000020004008080,
+    0x0021010810040002,
+    0x0800008C43020024,
+    0x0000800021005100,
+    0x0070201040008080,
+    0x0000D04282006A00,
+    0x0010014400080240,
+    0x0001080110050100,
+    0x0012000810240600,
+    0x0402000801040200,
+    0x028100108A004100,
+    0x0050800300102045,
+    0x8208210040120882,
+    0x8010600101183441,
+    0x020B000910006045,
+    0x0241001002480005,
+    0x0081000400880241,
+    0x0000009008024124,
+    0x0048122980410402,
 ];
-
-const ROOK_XRAY_SEED: [BitBoard; 64] = [
-    0x9208029202000400,
-    0x00200010000800A4,
-    0x0040100040080004,
-    0x0040080102400400,
-    0x0040040040020001,
-    0x0020008020010202,
-    0x0040010000800040,
-    0x12000185C106001C,
-    0x0100081020410400,
-    0x4100100008040010,
-    0x0000080402010008,
-    0x4088800410800208,
-    0x8200200200010020,
-    0x3000200100008020,
-    0x0800202000800040,
-    0x0A00200020004081,
-    0x0140002000100028,
-    0x4804001000080010,
-    0x4001000804020008,
-    0x0144002002002004,
-    0x0002001001008010,
-    0x0401002000802001,
-    0x0140040002A52810,
-    0x8441802000400020,
-    0x1140200010080011,
-    0x8010040008020009,
-    0x00000400100200F0,
-    0x0200040020200200,
-    0x50A0010020020020,
-    0x4000008020010020,
-    0x1100008020200040,
-    0x8003800020004020,
-    0x0840001000200024,
-    0x0600080400100010,
-    0x2000400880410010,
-    0x2480200200200400,
-    0x4802008040080008,
-    0x0820200080200100,
-    0x8080200080200040,
-    0x4109802000200040,
-    0x0440201004000806,
-    0x5010080102000400,
-    0x2100084040804201,
-    0x0440020004002020,
-    0x0060020001002020,
-    0x0850010000802020,
-    0x0810400080004001,
-    0x0000420088004204,
-    0x4840001000200020,
-    0x0004001000080010,
-    0x1100080401020008,
-    0x0080040020020020,
-    0x0040010020020020,
-    0x4008200100008020,
-    0x4400200040008020,
-    0x4081802000400020,
-    0x0080000D01183A91,
-    0x4000801165000841,
-    0x8041000822400411,
-    0x0840100420400802,
-    0x000C20003C100802,
-    0xB00C002310080402,
-    0x3A00033800804402,
-    0x0A800010E404894A,
+const BISHOP_MAGICS: [BitBoard; 64] = [
+    0x0848020822040013,
+    0x8010A40085821200,
+    0x0008008430840822,
+    0x0808048108040000,
+    0x1304042100008104,
+    0x5001012010204023,
+    0x81048801B8200420,
+    0x200A008084012000,
+    0x0040102001042084,
+    0x840A505042428020,
+    0x0000700102202920,
+    0x44101C0C10800002,
+    0x0040040422000000,
+    0x0180020802090202,
+    0x4020020811041202,
+    0x000104308C042000,
+    0x4140661002424400,
+    0x0028012008010460,
+    0x0188062102002A00,
+    0x0014004840102008,
+    0x0105000290400002,
+    0x8001022200410400,
+    0x104A041918013446,
+    0x008A000082008238,
+    0x04A0060008100430,
+    0x0008220008820801,
+    0x2508041208005010,
+    0x4008080200202020,
+    0x2441001013004000,
+    0x0030008060407000,
+    0x4008108000420800,
+    0x0012021050290100,
+    0x0210080482200500,
+    0xCC01112048100480,
+    0x0020402806500440,
+    0x00048E0080580080,
+    0x0040102020020080,
+    0x0028010440080807,
+    0x4601041108008800,
+    0x8040810E04104200,
+    0x901210110400088A,
+    0xA003080212081050,
+    0x00C1004048401004,
+    0x900000A014400800,
+    0x0008021040405401,
+    0x4020008206002090,
+    0x0004190424030100,
+    0x0424008A02026250,
+    0x8004088250900040,
+    0x1C00430088A04200,
+    0x0001020094040001,
+    0x8040210020880061,
+    0x2010040450442032,
+    0x0800840850044001,
+    0x0004040802140004,
+    0x0004080A04222020,
+    0x8088802110022000,
+    0x1081A10416114400,
+    0x0205010A24060820,
+    0x0000000720411080,
+    0x1008000208430400,
+    0x580C026028810840,
+    0x802020441020A110,
+    0x12C0022401020018,
 ];
-
-const BISHOP_XRAY_SEED: [BitBoard; 64] = [
-    0x0000440020404001,
-    0x00004010041C0149,
-    0x4000091040040003,
-    0x1012082008140000,
-    0x0069041004010200,
-    0x00C4820803000108,
-    0x0022410410400000,
-    0x0040021040020020,
-    0x4020A41040400104,
-    0x000C004040080020,
-    0x8086001010006800,
-    0x0D08410004011010,
-    0x0008400400200001,
-    0x1482000101004200,
-    0x0120021040020020,
-    0xAA08880210008010,
-    0x0800240040404001,
-    0x0000041100101002,
-    0xC412003000802B00,
-    0x1028202904001000,
-    0x0402080100820005,
-    0x0100100080040101,
-    0x40D8021080008020,
-    0x0008021120004008,
-    0x2040048002002008,
-    0x8080800400202004,
-    0x0300020004002008,
-    0x0000608000088010,
-    0x0200200800030100,
-    0x9084001010200400,
-    0x0000080080202009,
-    0x0A08080200002004,
-    0x1000058009008020,
-    0x8100030010004011,
-    0x6080008020004006,
-    0x0080004010010030,
-    0x8820010040040040,
-    0x0420008020010004,
-    0x0009000802202001,
-    0x2C00100400401002,
-    0x4000400200200010,
-    0x4200008080104029,
-    0x0000080100808400,
-    0x20C1406005000800,
-    0x0820010020032008,
-    0x00400300C0800900,
-    0x0000810040090104,
-    0x0280080008004002,
-    0x1608004010802002,
-    0x4300004008410004,
-    0x0208801000800C00,
-    0x0200000000401000,
-    0x0118020680200208,
-    0x0000010040024100,
-    0x0088020080100400,
-    0x8001001020080060,
-    0x8000004008410020,
-    0x0C08000020084010,
-    0x1000030410088008,
-    0x0240808800004010,
-    0x8000800000802002,
-    0x1C00860140601001,
-    0x006F020000401002,
-    0x1008020080100401,
+const ROOK_XRAY_MAGICS: [BitBoard; 64] = [
+    0x0080104000208000,
+    0x4040200040001004,
+    0x1100084011002004,
+    0x0900100005A10008,
+    0x0280040018008012,
+    0x980004402001020A,
+    0x4020202001100880,
+    0x01000A0040209100,
+    0x0880802040008002,
+    0x0000400020005000,
+    0x2000080420100200,
+    0x0842808010008800,
+    0x800A001120040A00,
+    0x0808001010400602,
+    0x140480800A000100,
+    0x1002000482430412,
+    0x4040088000804020,
+    0x440000C000D00020,
+    0x0800000810A00250,
+    0x0020008008001040,
+    0x01C00040042850C2,
+    0x0400012010080400,
+    0x0020008001200200,
+    0x0082020000608104,
+    0x1008400880008020,
+    0x0420002040005000,
+    0x0028020010001400,
+    0x0080100080800800,
+    0x6080010300080010,
+    0x0000030800104420,
+    0x2884100400C20801,
+    0x3080134200008504,
+    0x2000884008800022,
+    0x2800804000802004,
+    0x2041842000081040,
+    0x0000201001000900,
+    0x1100040080800802,
+    0x0008204098001015,
+    0x00400A180C002110,
+    0x4200084502000094,
+    0x0080800849090000,
+    0x0421009022020002,
+    0x40044080A01A0000,
+    0x00A0201001030000,
+    0x8108280402004000,
+    0x5000080205480028,
+    0x0003009002006000,
+    0x010203001D608000,
+    0x008048800B290100,
+    0x0000200080400180,
+    0x2400402204108200,
+    0x001000C800821180,
+    0x6004008004080080,
+    0x0002080204402800,
+    0x0400100201080400,
+    0x0000006C010E8200,
+    0x8140104100800029,
+    0x0000120100204482,
+    0x0000100844200101,
+    0x0404900009002025,
+    0x8002001021080402,
+    0x4580046030410208,
+    0x8400102A08880144,
+    0x0000A04400890032,
+];
+const BISHOP_XRAY_MAGICS: [BitBoard; 64] = [
+    0x4010010801044600,
+    0x0051020801002044,
+    0x2001080A80404040,
+    0xA011204180000100,
+    0x0001022080000400,
+    0x0001022406020005,
+    0x04240404020A2002,
+    0x200040880101608A,
+    0x00008420A8050120,
+    0x0008B09805004082,
+    0x1064023411020800,
+    0x0001041200209001,
+    0x0004040420600000,
+    0x1000A20104214000,
+    0x5008208210300600,
+    0x0100088601108210,
+    0x0100000924031400,
+    0x0048000428080461,
+    0x9000001001801011,
+    0x8060000401001002,
+    0x0010010208041400,
+    0x0200000450880101,
+    0x0290400091082400,
+    0x0A05020021180200,
+    0x0002201212045000,
+    0x4008048220010200,
+    0x1020300908004040,
+    0xE0A8140020402028,
+    0x1200402004002044,
+    0x2002804408005000,
+    0x4604004020821000,
+    0x0200402201210800,
+    0x0002200400101080,
+    0x2004440200041010,
+    0x0021260041040109,
+    0x0100804080004200,
+    0x4002100424004020,
+    0x504A808400C80200,
+    0x40060900C0807088,
+    0x0010808090120210,
+    0x1004020804404088,
+    0x008200D008010001,
+    0x4810082090000001,
+    0x0082003400800000,
+    0x0000020242004008,
+    0x0163502102000000,
+    0x00080A00A4036000,
+    0x4001042892440002,
+    0x0C0A808410200020,
+    0xA081042202220042,
+    0x04003104A6900980,
+    0x0240288B41108030,
+    0x0020222004240501,
+    0x2002083204421000,
+    0x0020201101411004,
+    0x2060212909010C24,
+    0x0441088041084012,
+    0x0000691051100802,
+    0x0080401082080200,
+    0x800940A800C12208,
+    0x9040402410020208,
+    0x400840A012422612,
+    0x5118400282020220,
+    0x4040040112202100,
 ];
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::super::pseudomoves::{
+        ray_bishop_moves, ray_bishop_xray, ray_rook_moves, ray_rook_xray,
+    };
+    use super::{
+        bishop_moves, bishop_xray, init_tables, rook_moves, rook_xray, tables, tables_ready,
+        BitBoard, BISHOP_MASKS, ROOK_MASKS,
+    };
+
+    //Every subset of the mask on every square, once on its own and once with pieces on the squares outside the mask
+    fn check_all(
+        masks: &[BitBoard; 64],
+        magic: fn(usize, BitBoard) -> BitBoard,
+        ray: fn(usize, BitBoard) -> BitBoard,
+    ) {
+        let mut rng = rand::thread_rng();
+        for (square, &mask) in masks.iter().enumerate() {
+            let mut blockers: BitBoard = 0;
+            loop {
+                let outside = rng.gen::<BitBoard>() & !mask & !(1 << square);
+                for occupied in [blockers, blockers | outside] {
+                    assert_eq!(
+                        magic(square, !occupied),
+                        ray(square, !occupied),
+                        "square {} occupied {:#x}",
+                        square,
+                        occupied
+                    );
+                }
+                blockers = blockers.wrapping_sub(mask) & mask;
+                if blockers == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tables_are_shared() {
+        init_tables();
+        assert!(tables_ready());
+        assert_eq!(tables().rook.len(), 102400);
+        assert_eq!(tables().bishop.len(), 5248);
+        //Initialising again keeps the same tables
+        let rook = tables().rook.as_ptr();
+        init_tables();
+        assert_eq!(tables().rook.as_ptr(), rook);
+    }
+
+    #[test]
+    fn matches_ray_scans() {
+        check_all(&ROOK_MASKS, rook_moves, ray_rook_moves);
+        check_all(&BISHOP_MASKS, bishop_moves, ray_bishop_moves);
+        check_all(&ROOK_MASKS, rook_xray, ray_rook_xray);
+        check_all(&BISHOP_MASKS, bishop_xray, ray_bishop_xray);
+    }
+}
@@ -1,6 +1,5 @@
 use std::{
     collections::HashMap,
-    f32::INFINITY,
     hash::{BuildHasherDefault, Hash, Hasher},
     mem, pin,
     sync::{Arc, Mutex},
@@ -8,22 +7,42 @@ use std::{
 
 use crate::Piece;
 
-use self::{move_generation::*, opening::OpeningBook, pseudomoves::*, utils::*};
+use self::{
+    board_move::Move,
+    evaluation::Evaluator,
+    history::History,
+    move_generation::*,
+    opening::OpeningBook,
+    pseudomoves::*,
+    search::{SearchInfo, SearchLimits},
+    transposition::TranspositionTable,
+    utils::*,
+};
+
+//Slider attack tables are shared by the whole program, these build them and tell whether they have been built
+pub use self::magic_bitboards::{init_tables, tables_ready};
 
 pub mod board_move;
+pub mod book_builder;
+pub mod evaluation;
+mod heuristics;
+pub mod history;
+mod king_safety;
 pub mod single_moves;
 //Goal is to make this private
 mod magic_bitboards;
 mod move_generation;
+mod move_picker;
 pub mod opening;
+mod pawns;
+mod perft;
+pub mod pgn;
 mod pseudomoves;
-mod search;
+pub mod search;
+pub mod transposition;
 mod utils;
 mod zobrist;
 
-const CHECKMATE_VALUE: f32 = 1000000.0;
-const CHECKMATE_THRESHOLD: f32 = 100000.0;
-
 type BitBoard = u64;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -51,6 +70,14 @@ pub struct Board {
     en_passant: BitBoard,
 
     pub white_to_play: bool,
+
+    //Half moves since the last capture or pawn move
+    pub halfmove_clock: u16,
+    pub fullmove_number: u16,
+
+    zobrist_key: u64,
+    //Key of the pawns alone, for the pawn structure cache
+    pawn_key: u64,
 }
 
 impl Board {
@@ -78,6 +105,12 @@ impl Board {
             en_passant: 0,
 
             white_to_play: true,
+
+            halfmove_clock: 0,
+            fullmove_number: 1,
+
+            zobrist_key: 0,
+            pawn_key: 0,
         }
     }
 
@@ -91,7 +124,7 @@ impl Board {
         !self.occupied_by_white
     }
 
-    pub fn to_fen(&self) -> String {
+    pub fn to_fen(self) -> String {
         let mut res = String::new();
         for y in 0..8 {
             let mut blank_spaces = 0;
@@ -136,7 +169,7 @@ impl Board {
                 res.push_str(&blank_spaces.to_string());
             }
             if y != 7 {
-                res.push_str("/");
+                res.push('/');
             }
         }
         res.push_str(&format!(" {} ", if self.white_to_play { 'w' } else { 'b' }));
@@ -176,6 +209,10 @@ impl Board {
             0x800000000000 => res.push_str("h6"),
             _ => res.push('-'),
         }
+        res.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock, self.fullmove_number
+        ));
         res
     }
 
@@ -184,7 +221,7 @@ impl Board {
         if split.len() != 6 {
             return Err(FENError::IncorrrectNumberOfTokens);
         }
-        let ranks: Vec<&str> = split.get(0).unwrap().split("/").collect();
+        let ranks: Vec<&str> = split.first().unwrap().split("/").collect();
         if ranks.len() != 8 {
             return Err(FENError::IncorrrectNumberOfRanks);
         }
@@ -266,40 +303,65 @@ impl Board {
             _ => return Err(FENError::InvalidEnPassant),
         }
 
+        res.halfmove_clock = match split.get(4).unwrap().parse() {
+            Ok(clock) => clock,
+            Err(_) => return Err(FENError::InvalidHalfmoveClock),
+        };
+        res.fullmove_number = match split.get(5).unwrap().parse() {
+            Ok(number) => number,
+            Err(_) => return Err(FENError::InvalidFullmoveNumber),
+        };
+
         res.redo_occupied();
+        res.zobrist_key = res.compute_zobrist();
+        res.pawn_key = res.compute_pawn_key();
         Ok(res)
     }
 
-    #[inline]
-    pub fn hueristic(&self) -> f32 {
-        let mut total: f32 = 0.;
-        total +=
-            (self.white_queens.count_ones() as f32 - self.black_queens.count_ones() as f32) * 9.;
-        total += (self.white_rooks.count_ones() as f32 - self.black_rooks.count_ones() as f32) * 5.;
-        total +=
-            (self.white_bishops.count_ones() as f32 - self.black_bishops.count_ones() as f32) * 3.;
-        total +=
-            (self.white_knights.count_ones() as f32 - self.black_knights.count_ones() as f32) * 3.;
-        total += (self.white_pawns.count_ones() as f32 - self.black_pawns.count_ones() as f32) * 1.;
-
-        total += self.under_attack_by_white().count_ones() as f32 * 0.25;
-        total -= self.under_attack_by_black().count_ones() as f32 * 0.25;
-        total
-    }
-
-    pub fn find_move(
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_move<E: Evaluator, R: FnMut(&SearchInfo)>(
         &self,
-        depth: usize,
+        limits: &SearchLimits,
         age: usize,
-        trans_table: &mut HashMap<Board, TransEntry>,
-        opening_book: &OpeningBook,
-    ) -> (Board, f32) {
-        let score = match opening_book.get_move(self.zobrist()) {
-            Some(book_move) => (self.make_move(book_move), 0.),
-            None => self.iterative_search(depth, age, trans_table),
+        trans_table: &mut TranspositionTable,
+        opening_books: &[OpeningBook],
+        history: &mut History,
+        evaluator: &mut E,
+        report: R,
+    ) -> FoundMove {
+        //Books are tried in order, a later book is only used when the ones before it have nothing legal. An infinite
+        //search is analysis, the books have nothing to say there.
+        let mut book_move = None;
+        let mut book_dropped = 0;
+        let opening_books = if limits.infinite { &[] } else { opening_books };
+        for book in opening_books {
+            let lookup = book.lookup(self);
+            book_dropped += lookup.dropped;
+            if lookup.book_move.is_some() {
+                book_move = lookup.book_move;
+                break;
+            }
+        }
+        let (best_move, score, pv) = match book_move {
+            Some(book_move) => (Some(book_move), 0, vec![book_move]),
+            None => {
+                let info =
+                    self.iterative_search(limits, age, trans_table, history, evaluator, report);
+                (info.best_move(), info.score, info.pv)
+            }
         };
-        trans_table.retain(|_, v| (v.age - age) < 2);
-        score
+        FoundMove {
+            best_move,
+            score,
+            pv,
+            from_book: book_move.is_some(),
+            book_dropped,
+        }
+    }
+
+    //Half moves played since the start of the game
+    pub fn game_ply(&self) -> usize {
+        (self.fullmove_number.max(1) as usize - 1) * 2 + !self.white_to_play as usize
     }
 
     fn get_en_passant(&self) -> BitBoard {
@@ -310,12 +372,11 @@ impl Board {
             {
                 return self.en_passant;
             }
-        } else if self.en_passant & 0xff0000000000 != 0 {
-            if (self.white_pawns & south_east_one(self.en_passant) != 0)
-                | (self.white_pawns & south_west_one(self.en_passant) != 0)
-            {
-                return self.en_passant;
-            }
+        } else if (self.en_passant & 0xff0000000000 != 0)
+            && ((self.white_pawns & south_east_one(self.en_passant) != 0)
+                | (self.white_pawns & south_west_one(self.en_passant) != 0))
+        {
+            return self.en_passant;
         }
         0
     }
@@ -356,7 +417,7 @@ impl Board {
 
 impl Default for Board {
     fn default() -> Self {
-        Board {
+        let mut board = Board {
             white_kings: 0x0000000000000010,
             white_queens: 0x0000000000000008,
             white_rooks: 0x0000000000000081,
@@ -379,16 +440,28 @@ impl Default for Board {
             en_passant: 0,
 
             white_to_play: true,
-        }
+
+            halfmove_clock: 0,
+            fullmove_number: 1,
+
+            zobrist_key: 0,
+            pawn_key: 0,
+        };
+        board.zobrist_key = board.compute_zobrist();
+        board.pawn_key = board.compute_pawn_key();
+        board
     }
 }
 
-pub struct TransEntry {
-    depth: usize,
-    lower_bound: f32,
-    upper_bound: f32,
-    response: Board,
-    age: usize,
+pub struct FoundMove {
+    pub best_move: Option<Move>,
+    //Centipawns from white's point of view
+    pub score: i32,
+    //Line the search expects to be played, just the move itself for a book move
+    pub pv: Vec<Move>,
+    pub from_book: bool,
+    //Book entries that weren't legal in the position and were skipped
+    pub book_dropped: usize,
 }
 
 #[derive(Debug)]
@@ -400,6 +473,8 @@ pub enum FENError {
     InvalidRankLength,
     InvalidTurnToken,
     InvalidEnPassant,
+    InvalidHalfmoveClock,
+    InvalidFullmoveNumber,
 }
 
 impl std::hash::Hash for Board {
@@ -407,7 +482,32 @@ impl std::hash::Hash for Board {
     where
         H: std::hash::Hasher,
     {
-        state.write_u64(self.occupied);
+        state.write_u64(self.zobrist_key);
         state.finish();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{board_move::Move, Board};
+
+    #[test]
+    fn fen_round_trip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq - 37 112",
+            "8/8/8/8/8/8/8/K1k5 w - - 99 250",
+        ] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+        }
+        //Both counters move on with the moves played
+        let board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K2R w K - 7 30").unwrap();
+        let quiet = board.make_move(Move::from_uci("h1h5").unwrap());
+        assert_eq!(quiet.to_fen(), "4k3/8/8/7R/8/8/4P3/4K3 b - - 8 30");
+        let reply = quiet.make_move(Move::from_uci("e8d8").unwrap());
+        assert_eq!(reply.to_fen(), "3k4/8/8/7R/8/8/4P3/4K3 w - - 9 31");
+        let pawn = reply.make_move(Move::from_uci("e2e3").unwrap());
+        assert_eq!(pawn.halfmove_clock, 0);
+    }
+}
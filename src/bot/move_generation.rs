@@ -1,5 +1,7 @@
 use std::pin;
 
+use super::board_move::{Move, MoveList, PromotionPiece};
+use super::magic_bitboards::*;
 use super::pseudomoves::*;
 use super::utils::*;
 use super::{BitBoard, Board};
@@ -7,6 +9,34 @@ use super::{BitBoard, Board};
 //Abandon all hope ye who enter here
 //This code was written over a long period of time. Its fast (about 50 million moves per second), its repetitive, but it work.
 
+const FIRST_RANK: BitBoard = 0x00000000000000FF;
+const EIGHTH_RANK: BitBoard = 0xFF00000000000000;
+
+//Which moves to generate. Captures and promotions are noisy, everything else is quiet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveKind {
+    Noisy,
+    Quiet,
+    All,
+}
+
+impl MoveKind {
+    //Squares pieces may move to and squares pawns may be pushed to
+    #[inline]
+    fn targets(
+        self,
+        enemy: BitBoard,
+        empty: BitBoard,
+        last_rank: BitBoard,
+    ) -> (BitBoard, BitBoard) {
+        match self {
+            MoveKind::Noisy => (enemy, empty & last_rank),
+            MoveKind::Quiet => (empty, empty & !last_rank),
+            MoveKind::All => (enemy | empty, empty),
+        }
+    }
+}
+
 //Black moves here
 impl Board {
     #[inline]
@@ -104,306 +134,180 @@ impl Board {
     }
 
     #[inline]
-    pub fn black_moves(&self) -> Vec<(Board, i32)> {
-        let mut res: Vec<(Board, i32)> = Vec::with_capacity(40);
+    pub fn black_move_list(&self) -> Vec<Move> {
+        let mut res = MoveList::new();
+        self.black_moves_into(MoveKind::All, !0, &mut res);
+        res.to_vec()
+    }
+
+    //Adds the moves of the given kind made by the pieces in pieces
+    #[inline]
+    pub fn black_moves_into(&self, kind: MoveKind, pieces: BitBoard, res: &mut MoveList) {
         let checkmask = self.black_checkmask();
         let pinmask_d = self.black_pinmask_d();
         let pinmask_hv = self.black_pinmask_hv();
         let pinmask = pinmask_d | pinmask_hv;
+        let (targets, push_targets) =
+            kind.targets(self.occupied_by_white, !self.occupied, FIRST_RANK);
         //Unpinned rooks
-        for i in BitBoardIter(self.black_rooks & !pinmask) {
-            let moves = rook_moves(i, !self.occupied) & checkmask & self.white_or_empty();
+        for i in BitBoardIter(self.black_rooks & pieces & !pinmask) {
+            let moves = rook_moves(i, !self.occupied) & checkmask & targets;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_rooks ^= piece_mask | new_square;
-                let score = new_board.capture_white(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                new_board.castle &= !piece_mask;
-                res.push((new_board, score + 2));
-            }
-        }
-        //Pinned rooks
-        for i in BitBoardIter(self.black_rooks & pinmask) {
-            let moves =
-                rook_moves(i, !self.occupied) & checkmask & self.white_or_empty() & pinmask_hv;
+                res.push(self.simple_move(i, m));
+            }
+        }
+        //Pinned rooks, a rook pinned along a diagonal can never move
+        for i in BitBoardIter(self.black_rooks & pieces & pinmask_hv) {
+            let moves = rook_moves(i, !self.occupied) & checkmask & targets & pinmask_hv;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_rooks ^= piece_mask | new_square;
-                let score = new_board.capture_white(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                new_board.castle &= !piece_mask;
-                res.push((new_board, score + 2));
+                res.push(self.simple_move(i, m));
             }
         }
 
         //Unpinned Bishops
-        for i in BitBoardIter(self.black_bishops & !pinmask) {
-            let moves = bishop_moves(i, !self.occupied) & checkmask & self.white_or_empty();
+        for i in BitBoardIter(self.black_bishops & pieces & !pinmask) {
+            let moves = bishop_moves(i, !self.occupied) & checkmask & targets;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_bishops ^= piece_mask | new_square;
-                let score = new_board.capture_white(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, score + 3));
-            }
-        }
-        //Pinned Bishops
-        for i in BitBoardIter(self.black_bishops & pinmask) {
-            let moves =
-                bishop_moves(i, !self.occupied) & checkmask & self.white_or_empty() & pinmask_d;
+                res.push(self.simple_move(i, m));
+            }
+        }
+        //Pinned Bishops, a bishop pinned along a rank or file can never move
+        for i in BitBoardIter(self.black_bishops & pieces & pinmask_d) {
+            let moves = bishop_moves(i, !self.occupied) & checkmask & targets & pinmask_d;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_bishops ^= piece_mask | new_square;
-                let score = new_board.capture_white(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, score + 3));
+                res.push(self.simple_move(i, m));
             }
         }
 
         //Unpinned Queens
-        for i in BitBoardIter(self.black_queens & !pinmask) {
+        for i in BitBoardIter(self.black_queens & pieces & !pinmask) {
             let moves = (rook_moves(i, !self.occupied) | bishop_moves(i, !self.occupied))
                 & checkmask
-                & self.white_or_empty();
+                & targets;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_queens ^= piece_mask | new_square;
-                let score = new_board.capture_white(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, score + 1));
+                res.push(self.simple_move(i, m));
             }
         }
         //Pinned Queens
-        for i in BitBoardIter(self.black_queens & pinmask) {
+        for i in BitBoardIter(self.black_queens & pieces & pinmask_d) {
             //diagonal moves
-            let moves =
-                bishop_moves(i, !self.occupied) & checkmask & self.white_or_empty() & pinmask_d;
+            let moves = bishop_moves(i, !self.occupied) & checkmask & targets & pinmask_d;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_queens ^= piece_mask | new_square;
-                let score = new_board.capture_white(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, score + 1));
+                res.push(self.simple_move(i, m));
             }
+        }
+        for i in BitBoardIter(self.black_queens & pieces & pinmask_hv) {
             //hv moves
-            let moves =
-                rook_moves(i, !self.occupied) & checkmask & self.white_or_empty() & pinmask_hv;
+            let moves = rook_moves(i, !self.occupied) & checkmask & targets & pinmask_hv;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_queens ^= piece_mask | new_square;
-                let score = new_board.capture_white(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, score + 1));
+                res.push(self.simple_move(i, m));
             }
         }
 
         //A pinned knight can never move
-        for i in BitBoardIter(self.black_knights & !pinmask) {
-            let moves = KNIGHT_MOVES[i] & checkmask & self.white_or_empty();
+        for i in BitBoardIter(self.black_knights & pieces & !pinmask) {
+            let moves = KNIGHT_MOVES[i] & checkmask & targets;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_knights ^= piece_mask | new_square;
-                let score = new_board.capture_white(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, score + 4));
+                res.push(self.simple_move(i, m));
             }
         }
 
         //Unpinned Pawn Pushes
-        for i in BitBoardIter(self.black_pawns & !pinmask & north_one(!self.occupied)) {
-            let moves = PAWN_MOVES[1][i] & checkmask & !self.occupied;
+        for i in BitBoardIter(self.black_pawns & pieces & !pinmask & north_one(!self.occupied)) {
+            let moves = PAWN_MOVES[1][i] & checkmask & push_targets;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                if i - m == 16 {
-                    new_board.en_passant |= north_one(new_square);
-                }
-                new_board.black_pawns ^= piece_mask;
                 if m < 8 {
-                    let mut queen_board = new_board.clone();
-                    queen_board.black_queens |= new_square;
-                    queen_board.redo_occupied();
-                    queen_board.white_to_play = true;
-                    res.push((queen_board, 1000));
-                    let mut rook_board = new_board.clone();
-                    rook_board.black_rooks |= new_square;
-                    rook_board.redo_occupied();
-                    rook_board.white_to_play = true;
-                    res.push((rook_board, 900));
-                    let mut knight_board = new_board.clone();
-                    knight_board.black_knights |= new_square;
-                    knight_board.redo_occupied();
-                    knight_board.white_to_play = true;
-                    res.push((knight_board, 700));
-                    let mut bishop_board = new_board.clone();
-                    bishop_board.black_bishops |= new_square;
-                    bishop_board.redo_occupied();
-                    bishop_board.white_to_play = true;
-                    res.push((bishop_board, 800));
+                    push_promotions(res, i, m, Move::QUIET);
+                } else if i - m == 16 {
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::DOUBLE_PUSH));
                 } else {
-                    new_board.black_pawns |= new_square;
-                    new_board.redo_occupied();
-                    new_board.white_to_play = true;
-                    res.push((new_board, 5));
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::QUIET));
                 }
             }
         }
 
         //Pinned Pawn Pushes
-        for i in BitBoardIter(self.black_pawns & pinmask & north_one(!self.occupied)) {
-            let moves = PAWN_MOVES[1][i] & checkmask & !self.occupied & pinmask_hv;
+        for i in BitBoardIter(self.black_pawns & pieces & pinmask_hv & north_one(!self.occupied)) {
+            let moves = PAWN_MOVES[1][i] & checkmask & push_targets & pinmask_hv;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
                 if i - m == 16 {
-                    new_board.en_passant |= north_one(new_square);
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::DOUBLE_PUSH));
+                } else {
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::QUIET));
                 }
-                new_board.black_pawns ^= piece_mask | new_square;
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, 6));
             }
         }
 
         //Unpinned Pawn Attacks
-        for i in BitBoardIter(self.black_pawns & !pinmask) {
-            let moves = PAWN_ATTACKS[1][i] & checkmask & self.occupied_by_white;
+        for i in BitBoardIter(self.black_pawns & pieces & !pinmask) {
+            let moves = PAWN_ATTACKS[1][i] & checkmask & targets & self.occupied_by_white;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_pawns ^= piece_mask | new_square;
-                let score = new_board.capture_white(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, score + 6));
+                if m < 8 {
+                    push_promotions(res, i, m, Move::CAPTURE);
+                } else {
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::CAPTURE));
+                }
             }
         }
 
         //Pinned Pawn Attacks
-        for i in BitBoardIter(self.black_pawns & pinmask) {
-            let moves = PAWN_ATTACKS[1][i] & checkmask & self.occupied_by_white & pinmask_d;
-            for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_pawns ^= piece_mask | new_square;
-                let score = new_board.capture_white(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, score + 6));
-            }
-        }
-
-        //Unpinned En Passant
-        for i in BitBoardIter(self.black_pawns & !pinmask) {
-            let moves = PAWN_ATTACKS[0][i] & checkmask & self.en_passant;
+        for i in BitBoardIter(self.black_pawns & pieces & pinmask_d) {
+            let moves =
+                PAWN_ATTACKS[1][i] & checkmask & targets & self.occupied_by_white & pinmask_d;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_pawns ^= piece_mask | new_square;
-                let score = new_board.capture_white(north_one(new_square));
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, score + 6));
+                if m < 8 {
+                    push_promotions(res, i, m, Move::CAPTURE);
+                } else {
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::CAPTURE));
+                }
             }
         }
 
-        //Pinned En Passant
-        for i in BitBoardIter(self.black_pawns & pinmask) {
-            let moves = PAWN_ATTACKS[0][i] & checkmask & self.en_passant & pinmask_d;
+        //En Passant
+        //Taking the pawn that gave check also gets out of check. Pins are checked on the board after the capture since
+        //two pawns leave the same rank at once.
+        let en_passant_mask = if kind == MoveKind::Quiet {
+            0
+        } else if checkmask & north_one(self.en_passant) != 0 {
+            self.en_passant
+        } else {
+            checkmask & self.en_passant
+        };
+        for i in BitBoardIter(self.black_pawns & pieces) {
+            let moves = PAWN_ATTACKS[1][i] & en_passant_mask;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.black_pawns ^= piece_mask | new_square;
-                let score = new_board.capture_white(north_one(new_square));
-                new_board.redo_occupied();
-                new_board.white_to_play = true;
-                res.push((new_board, score + 6));
+                if self.en_passant_is_legal(i, m, north_one(1 << m)) {
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::EN_PASSANT));
+                }
             }
         }
 
         //King Moves
+        if self.black_kings & pieces == 0 {
+            return;
+        }
         let king_square = self.black_kings.trailing_zeros() as usize;
         let under_attack = self.under_attack_by_white();
-        let moves = KING_MOVES[king_square] & !under_attack & self.white_or_empty();
+        let moves = KING_MOVES[king_square] & !under_attack & targets;
         for m in BitBoardIter(moves) {
-            let new_square = (1 as u64) << m;
-            let mut new_board = self.clone();
-            new_board.en_passant = 0;
-            new_board.black_kings = new_square;
-            let score = new_board.capture_white(new_square);
-            new_board.redo_occupied();
-            new_board.white_to_play = true;
-            new_board.castle &= !0xff00000000000000;
-            res.push((new_board, score));
+            res.push(self.simple_move(king_square, m));
         }
 
         //Castleing
-        if (self.castle & 0x9000000000000000 == 0x9000000000000000)
+        if (kind != MoveKind::Noisy)
+            && (self.castle & 0x9000000000000000 == 0x9000000000000000)
             && under_attack & 0x7000000000000000 == 0
+            && self.occupied & 0x6000000000000000 == 0
         {
-            let mut new_board = self.clone();
-            new_board.black_rooks ^= 0xa000000000000000;
-            new_board.black_kings ^= 0x5000000000000000;
-            new_board.white_to_play = true;
-            new_board.redo_occupied();
-            res.push((new_board, 0));
-        }
-        if (self.castle & 0x1100000000000000 == 0x1100000000000000)
+            res.push(Move::new(60, 62, PromotionPiece::None, Move::CASTLE));
+        }
+        if (kind != MoveKind::Noisy)
+            && (self.castle & 0x1100000000000000 == 0x1100000000000000)
             && under_attack & 0x1c00000000000000 == 0
+            && self.occupied & 0x0e00000000000000 == 0
         {
-            let mut new_board = self.clone();
-            new_board.black_rooks ^= 0x900000000000000;
-            new_board.black_kings ^= 0x1400000000000000;
-            new_board.white_to_play = true;
-            new_board.redo_occupied();
-            res.push((new_board, 0));
+            res.push(Move::new(60, 58, PromotionPiece::None, Move::CASTLE));
         }
-
-        res
     }
 
     #[inline]
@@ -508,303 +412,180 @@ impl Board {
     }
 
     #[inline]
-    pub fn white_moves(&self) -> Vec<(Board, i32)> {
-        let mut res: Vec<(Board, i32)> = Vec::with_capacity(40);
+    pub fn white_move_list(&self) -> Vec<Move> {
+        let mut res = MoveList::new();
+        self.white_moves_into(MoveKind::All, !0, &mut res);
+        res.to_vec()
+    }
+
+    //Adds the moves of the given kind made by the pieces in pieces
+    #[inline]
+    pub fn white_moves_into(&self, kind: MoveKind, pieces: BitBoard, res: &mut MoveList) {
         let checkmask = self.white_checkmask();
         let pinmask_d = self.white_pinmask_d();
         let pinmask_hv = self.white_pinmask_hv();
         let pinmask = pinmask_d | pinmask_hv;
+        let (targets, push_targets) =
+            kind.targets(self.occupied_by_black, !self.occupied, EIGHTH_RANK);
         //Unpinned rooks
-        for i in BitBoardIter(self.white_rooks & !pinmask) {
-            let moves = rook_moves(i, !self.occupied) & checkmask & self.black_or_empty();
+        for i in BitBoardIter(self.white_rooks & pieces & !pinmask) {
+            let moves = rook_moves(i, !self.occupied) & checkmask & targets;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_rooks ^= piece_mask | new_square;
-                let score = new_board.capture_black(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                new_board.castle &= !piece_mask;
-                res.push((new_board, score + 2));
-            }
-        }
-        //Pinned rooks
-        for i in BitBoardIter(self.white_rooks & pinmask) {
-            let moves =
-                rook_moves(i, !self.occupied) & checkmask & self.black_or_empty() & pinmask_hv;
+                res.push(self.simple_move(i, m));
+            }
+        }
+        //Pinned rooks, a rook pinned along a diagonal can never move
+        for i in BitBoardIter(self.white_rooks & pieces & pinmask_hv) {
+            let moves = rook_moves(i, !self.occupied) & checkmask & targets & pinmask_hv;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_rooks ^= piece_mask | new_square;
-                let score = new_board.capture_black(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                new_board.castle &= !piece_mask;
-                res.push((new_board, score + 2));
+                res.push(self.simple_move(i, m));
             }
         }
 
         //Unpinned Bishops
-        for i in BitBoardIter(self.white_bishops & !pinmask) {
-            let moves = bishop_moves(i, !self.occupied) & checkmask & self.black_or_empty();
+        for i in BitBoardIter(self.white_bishops & pieces & !pinmask) {
+            let moves = bishop_moves(i, !self.occupied) & checkmask & targets;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_bishops ^= piece_mask | new_square;
-                let score = new_board.capture_black(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, score + 3));
-            }
-        }
-        //Pinned Bishops
-        for i in BitBoardIter(self.white_bishops & pinmask) {
-            let moves =
-                bishop_moves(i, !self.occupied) & checkmask & self.black_or_empty() & pinmask_d;
+                res.push(self.simple_move(i, m));
+            }
+        }
+        //Pinned Bishops, a bishop pinned along a rank or file can never move
+        for i in BitBoardIter(self.white_bishops & pieces & pinmask_d) {
+            let moves = bishop_moves(i, !self.occupied) & checkmask & targets & pinmask_d;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_bishops ^= piece_mask | new_square;
-                let score = new_board.capture_black(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, score + 3));
+                res.push(self.simple_move(i, m));
             }
         }
 
         //Unpinned Queens
-        for i in BitBoardIter(self.white_queens & !pinmask) {
+        for i in BitBoardIter(self.white_queens & pieces & !pinmask) {
             let moves = (rook_moves(i, !self.occupied) | bishop_moves(i, !self.occupied))
                 & checkmask
-                & self.black_or_empty();
+                & targets;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_queens ^= piece_mask | new_square;
-                let score = new_board.capture_black(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, score + 1));
+                res.push(self.simple_move(i, m));
             }
         }
         //Pinned Queens
-        for i in BitBoardIter(self.white_queens & pinmask) {
+        for i in BitBoardIter(self.white_queens & pieces & pinmask_d) {
             //diagonal moves
-            let moves =
-                bishop_moves(i, !self.occupied) & checkmask & self.black_or_empty() & pinmask_d;
+            let moves = bishop_moves(i, !self.occupied) & checkmask & targets & pinmask_d;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_queens ^= piece_mask | new_square;
-                let score = new_board.capture_black(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, score + 1));
+                res.push(self.simple_move(i, m));
             }
+        }
+        for i in BitBoardIter(self.white_queens & pieces & pinmask_hv) {
             //hv moves
-            let moves =
-                rook_moves(i, !self.occupied) & checkmask & self.black_or_empty() & pinmask_hv;
+            let moves = rook_moves(i, !self.occupied) & checkmask & targets & pinmask_hv;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_queens ^= piece_mask | new_square;
-                let score = new_board.capture_black(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, score + 1));
+                res.push(self.simple_move(i, m));
             }
         }
 
         //A pinned knight can never move
-        for i in BitBoardIter(self.white_knights & !pinmask) {
-            let moves = KNIGHT_MOVES[i] & checkmask & self.black_or_empty();
+        for i in BitBoardIter(self.white_knights & pieces & !pinmask) {
+            let moves = KNIGHT_MOVES[i] & checkmask & targets;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_knights ^= piece_mask | new_square;
-                let score = new_board.capture_black(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, score + 4));
+                res.push(self.simple_move(i, m));
             }
         }
 
         //Unpinned Pawn Pushes
-        for i in BitBoardIter(
-            self.white_pawns & !pinmask & south_one(!self.occupied) & !0x00FF000000000000,
-        ) {
-            let moves = PAWN_MOVES[0][i] & checkmask & !self.occupied;
+        for i in BitBoardIter(self.white_pawns & pieces & !pinmask & south_one(!self.occupied)) {
+            let moves = PAWN_MOVES[0][i] & checkmask & push_targets;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                if m - i == 16 {
-                    new_board.en_passant |= south_one(new_square);
-                }
-                new_board.white_pawns ^= piece_mask;
                 if m > 55 {
-                    let mut queen_board = new_board.clone();
-                    queen_board.white_queens |= new_square;
-                    queen_board.redo_occupied();
-                    queen_board.white_to_play = false;
-                    res.push((queen_board, 1000));
-                    let mut rook_board = new_board.clone();
-                    rook_board.white_rooks |= new_square;
-                    rook_board.redo_occupied();
-                    rook_board.white_to_play = false;
-                    res.push((rook_board, 900));
-                    let mut knight_board = new_board.clone();
-                    knight_board.white_knights |= new_square;
-                    knight_board.redo_occupied();
-                    knight_board.white_to_play = false;
-                    res.push((knight_board, 700));
-                    let mut bishop_board = new_board.clone();
-                    bishop_board.white_bishops |= new_square;
-                    bishop_board.redo_occupied();
-                    bishop_board.white_to_play = false;
-                    res.push((bishop_board, 800));
+                    push_promotions(res, i, m, Move::QUIET);
+                } else if m - i == 16 {
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::DOUBLE_PUSH));
                 } else {
-                    new_board.white_pawns |= new_square;
-                    new_board.redo_occupied();
-                    new_board.white_to_play = false;
-                    res.push((new_board, 5));
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::QUIET));
                 }
             }
         }
 
         //Pinned Pawn Pushes
-        for i in BitBoardIter(self.white_pawns & pinmask & south_one(!self.occupied)) {
-            let moves = PAWN_MOVES[0][i] & checkmask & !self.occupied & pinmask_hv;
+        for i in BitBoardIter(self.white_pawns & pieces & pinmask_hv & south_one(!self.occupied)) {
+            let moves = PAWN_MOVES[0][i] & checkmask & push_targets & pinmask_hv;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
                 if m - i == 16 {
-                    new_board.en_passant |= south_one(new_square);
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::DOUBLE_PUSH));
+                } else {
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::QUIET));
                 }
-                new_board.white_pawns ^= piece_mask | new_square;
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, 5));
             }
         }
 
         //Unpinned Pawn Attacks
-        for i in BitBoardIter(self.white_pawns & !pinmask) {
-            let moves = PAWN_ATTACKS[0][i] & checkmask & self.occupied_by_black;
+        for i in BitBoardIter(self.white_pawns & pieces & !pinmask) {
+            let moves = PAWN_ATTACKS[0][i] & checkmask & targets & self.occupied_by_black;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_pawns ^= piece_mask | new_square;
-                let score = new_board.capture_black(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, score + 5));
+                if m > 55 {
+                    push_promotions(res, i, m, Move::CAPTURE);
+                } else {
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::CAPTURE));
+                }
             }
         }
 
         //Pinned Pawn Attacks
-        for i in BitBoardIter(self.white_pawns & pinmask) {
-            let moves = PAWN_ATTACKS[0][i] & checkmask & self.occupied_by_black & pinmask_d;
-            for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_pawns ^= piece_mask | new_square;
-                let score = new_board.capture_black(new_square);
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, score + 5));
-            }
-        }
-
-        //Unpinned En Passant
-        for i in BitBoardIter(self.white_pawns & !pinmask) {
-            let moves = PAWN_ATTACKS[0][i] & checkmask & self.en_passant;
+        for i in BitBoardIter(self.white_pawns & pieces & pinmask_d) {
+            let moves =
+                PAWN_ATTACKS[0][i] & checkmask & targets & self.occupied_by_black & pinmask_d;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_pawns ^= piece_mask | new_square;
-                let score = new_board.capture_black(south_one(new_square));
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, score + 5));
+                if m > 55 {
+                    push_promotions(res, i, m, Move::CAPTURE);
+                } else {
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::CAPTURE));
+                }
             }
         }
 
-        //Pinned En Passant
-        for i in BitBoardIter(self.white_pawns & pinmask) {
-            let moves = PAWN_ATTACKS[0][i] & checkmask & self.en_passant & pinmask_d;
+        //En Passant
+        //Taking the pawn that gave check also gets out of check. Pins are checked on the board after the capture since
+        //two pawns leave the same rank at once.
+        let en_passant_mask = if kind == MoveKind::Quiet {
+            0
+        } else if checkmask & south_one(self.en_passant) != 0 {
+            self.en_passant
+        } else {
+            checkmask & self.en_passant
+        };
+        for i in BitBoardIter(self.white_pawns & pieces) {
+            let moves = PAWN_ATTACKS[0][i] & en_passant_mask;
             for m in BitBoardIter(moves) {
-                let piece_mask = (1 as u64) << i;
-                let new_square = (1 as u64) << m;
-                let mut new_board = self.clone();
-                new_board.en_passant = 0;
-                new_board.white_pawns ^= piece_mask | new_square;
-                let score = new_board.capture_black(south_one(new_square));
-                new_board.redo_occupied();
-                new_board.white_to_play = false;
-                res.push((new_board, score + 5));
+                if self.en_passant_is_legal(i, m, south_one(1 << m)) {
+                    res.push(Move::new(i, m, PromotionPiece::None, Move::EN_PASSANT));
+                }
             }
         }
 
         //King Moves
+        if self.white_kings & pieces == 0 {
+            return;
+        }
         let king_square = self.white_kings.trailing_zeros() as usize;
         let under_attack = self.under_attack_by_black();
-        let moves = KING_MOVES[king_square] & !under_attack & self.black_or_empty();
+        let moves = KING_MOVES[king_square] & !under_attack & targets;
         for m in BitBoardIter(moves) {
-            let new_square = (1 as u64) << m;
-            let mut new_board = self.clone();
-            new_board.white_kings = new_square;
-            let score = new_board.capture_black(new_square);
-            new_board.redo_occupied();
-            new_board.white_to_play = false;
-            new_board.castle &= !0xff;
-            res.push((new_board, score));
+            res.push(self.simple_move(king_square, m));
         }
 
         //Castleing
-        if (self.castle & 0x90 == 0x90) && under_attack & 0x70 == 0 {
-            let mut new_board = self.clone();
-            new_board.white_rooks ^= 0xa0;
-            new_board.white_kings ^= 0x50;
-            new_board.white_to_play = false;
-            new_board.redo_occupied();
-            res.push((new_board, 0));
-        }
-        if (self.castle & 0x11 == 0x11) && under_attack & 0x1c == 0 {
-            let mut new_board = self.clone();
-            new_board.white_rooks ^= 0x9;
-            new_board.white_kings ^= 0x14;
-            new_board.white_to_play = false;
-            new_board.redo_occupied();
-            res.push((new_board, 0));
+        if (kind != MoveKind::Noisy)
+            && (self.castle & 0x90 == 0x90)
+            && under_attack & 0x70 == 0
+            && self.occupied & 0x60 == 0
+        {
+            res.push(Move::new(4, 6, PromotionPiece::None, Move::CASTLE));
+        }
+        if (kind != MoveKind::Noisy)
+            && (self.castle & 0x11 == 0x11)
+            && under_attack & 0x1c == 0
+            && self.occupied & 0xe == 0
+        {
+            res.push(Move::new(4, 2, PromotionPiece::None, Move::CASTLE));
         }
-
-        res
     }
 
     #[inline]
@@ -861,6 +642,120 @@ impl Board {
 }
 
 impl Board {
+    //Moves of the side to play
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if self.white_to_play {
+            self.white_move_list()
+        } else {
+            self.black_move_list()
+        }
+    }
+
+    //Every position the side to play can move to, with the move that gets there
+    pub fn successors(&self) -> Vec<(Move, Board)> {
+        self.legal_moves()
+            .into_iter()
+            .map(|m| (m, self.make_move(m)))
+            .collect()
+    }
+
+    //Adds the moves of the given kind the side to play can make with the pieces in pieces
+    #[inline]
+    pub fn generate_moves(&self, kind: MoveKind, pieces: BitBoard, res: &mut MoveList) {
+        if self.white_to_play {
+            self.white_moves_into(kind, pieces, res);
+        } else {
+            self.black_moves_into(kind, pieces, res);
+        }
+    }
+
+    //Whether the side to play has the move, only the moves of the piece on its from square are generated. Moves
+    //taken from the transposition table or remembered from other positions have to be checked before they are played.
+    pub fn is_legal(&self, m: Move) -> bool {
+        self.generates(m, MoveKind::All)
+    }
+
+    //Whether the move is among the moves of the given kind
+    pub fn generates(&self, m: Move, kind: MoveKind) -> bool {
+        let mut res = MoveList::new();
+        self.generate_moves(kind, 1 << m.from_square, &mut res);
+        res.contains(&m)
+    }
+
+    //Ordering score for a move, the value of whatever is captured plus a small bonus for the piece moving
+    #[inline]
+    pub fn move_score(&self, m: Move) -> i32 {
+        match m.promotion_piece {
+            PromotionPiece::Queen => return 1000,
+            PromotionPiece::Rook => return 900,
+            PromotionPiece::Bishop => return 800,
+            PromotionPiece::Knight => return 700,
+            PromotionPiece::None => (),
+        }
+        let from_mask: BitBoard = 1 << m.from_square;
+        let to_mask: BitBoard = 1 << m.to_square;
+        let captured = if (m.flags & Move::EN_PASSANT != 0)
+            | (to_mask & (self.white_pawns | self.black_pawns) != 0)
+        {
+            100
+        } else if to_mask & (self.white_knights | self.black_knights) != 0 {
+            200
+        } else if to_mask & (self.white_bishops | self.black_bishops) != 0 {
+            300
+        } else if to_mask & (self.white_rooks | self.black_rooks) != 0 {
+            400
+        } else if to_mask & (self.white_queens | self.black_queens) != 0 {
+            500
+        } else {
+            0
+        };
+        let moving = if from_mask & (self.white_pawns | self.black_pawns) != 0 {
+            5
+        } else if from_mask & (self.white_knights | self.black_knights) != 0 {
+            4
+        } else if from_mask & (self.white_bishops | self.black_bishops) != 0 {
+            3
+        } else if from_mask & (self.white_rooks | self.black_rooks) != 0 {
+            2
+        } else if from_mask & (self.white_queens | self.black_queens) != 0 {
+            1
+        } else {
+            0
+        };
+        captured + moving
+    }
+
+    //Whether the side to play is left in check by a slider once both pawns are off their squares
+    #[inline]
+    fn en_passant_is_legal(&self, from: usize, to: usize, captured: BitBoard) -> bool {
+        let occupied = (self.occupied ^ (1 << from) ^ captured) | (1 << to);
+        let (king, rooks, bishops) = if self.white_to_play {
+            (
+                self.white_kings,
+                self.black_rooks | self.black_queens,
+                self.black_bishops | self.black_queens,
+            )
+        } else {
+            (
+                self.black_kings,
+                self.white_rooks | self.white_queens,
+                self.white_bishops | self.white_queens,
+            )
+        };
+        let king_square = king.trailing_zeros() as usize;
+        (rook_moves(king_square, !occupied) & rooks == 0)
+            & (bishop_moves(king_square, !occupied) & bishops == 0)
+    }
+
+    #[inline]
+    fn simple_move(&self, from: usize, to: usize) -> Move {
+        if self.occupied & (1 << to) != 0 {
+            Move::new(from, to, PromotionPiece::None, Move::CAPTURE)
+        } else {
+            Move::new(from, to, PromotionPiece::None, Move::QUIET)
+        }
+    }
+
     #[inline]
     pub fn redo_occupied(&mut self) {
         self.occupied_by_white = self.white_pawns
@@ -878,3 +773,49 @@ impl Board {
         self.occupied = self.occupied_by_white | self.occupied_by_black;
     }
 }
+
+#[inline]
+fn push_promotions(res: &mut MoveList, from: usize, to: usize, flags: u8) {
+    res.push(Move::new(from, to, PromotionPiece::Queen, flags));
+    res.push(Move::new(from, to, PromotionPiece::Rook, flags));
+    res.push(Move::new(from, to, PromotionPiece::Knight, flags));
+    res.push(Move::new(from, to, PromotionPiece::Bishop, flags));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Board, Move, PromotionPiece};
+
+    fn moves(fen: &str) -> Vec<Move> {
+        Board::from_fen(fen).unwrap().legal_moves()
+    }
+
+    fn find(moves: &[Move], text: &str) -> Move {
+        *moves.iter().find(|m| m.to_uci() == text).unwrap()
+    }
+
+    #[test]
+    fn move_flags() {
+        let start = moves("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(start.len(), 20);
+        assert_eq!(find(&start, "e2e4").flags, Move::DOUBLE_PUSH);
+        assert_eq!(find(&start, "e2e3").flags, Move::QUIET);
+        assert_eq!(find(&start, "g1f3").flags, Move::QUIET);
+
+        let list = moves("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+        assert_eq!(find(&list, "e5d6").flags, Move::EN_PASSANT);
+        assert_eq!(find(&list, "e1g1").flags, Move::CASTLE);
+        assert_eq!(find(&list, "e1c1").flags, Move::CASTLE);
+        assert_eq!(find(&list, "a1a8").flags, Move::CAPTURE);
+        //Every promotion piece, both pushing and capturing
+        for piece in ["q", "r", "b", "n"] {
+            assert_eq!(find(&list, &format!("b7b8{}", piece)).flags, Move::QUIET);
+            assert_eq!(find(&list, &format!("b7a8{}", piece)).flags, Move::CAPTURE);
+        }
+        assert_eq!(find(&list, "b7b8n").promotion_piece, PromotionPiece::Knight);
+        //No move is generated twice
+        for (i, m) in list.iter().enumerate() {
+            assert!(!list[i + 1..].contains(m), "{}", m.to_uci());
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use super::{
+    board_move::{Move, MoveList, PromotionPiece, MAX_MOVES},
+    magic_bitboards::{bishop_moves, rook_moves},
+    move_generation::MoveKind,
+    pseudomoves::{KING_MOVES, KNIGHT_MOVES, PAWN_ATTACKS},
+    Board,
+};
+
+//Losing captures score below every winning one
+const LOSING_CAPTURE: i32 = -10000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenerateNoisy,
+    WinningCaptures,
+    Killers,
+    GenerateQuiets,
+    Quiets,
+    LosingCaptures,
+    Done,
+}
+
+//Hands out the moves of a position best first, generating them a stage at a time. A cutoff on the hash move costs
+//one piece's moves to check it is legal, a cutoff on a capture never generates the quiet moves.
+pub struct MovePicker<'a> {
+    board: &'a Board,
+    stage: Stage,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    killer_index: usize,
+    //Only captures and promotions are handed out
+    noisy_only: bool,
+    moves: MoveList,
+    scores: [i32; MAX_MOVES],
+    //Next move to hand out, and where the losing captures left behind by the winning ones start and end
+    index: usize,
+    losing_start: usize,
+    losing_end: usize,
+}
+
+impl<'a> MovePicker<'a> {
+    pub fn new(board: &'a Board, hash_move: Option<Move>, killers: [Option<Move>; 2]) -> Self {
+        MovePicker {
+            board,
+            stage: Stage::HashMove,
+            hash_move,
+            killers,
+            killer_index: 0,
+            noisy_only: false,
+            moves: MoveList::new(),
+            scores: [0; MAX_MOVES],
+            index: 0,
+            losing_start: 0,
+            losing_end: 0,
+        }
+    }
+
+    //Captures and promotions only, for the quiescence search
+    pub fn noisy(board: &'a Board) -> Self {
+        MovePicker {
+            stage: Stage::GenerateNoisy,
+            noisy_only: true,
+            ..MovePicker::new(board, None, [None; 2])
+        }
+    }
+
+    //quiet_score orders the quiet moves, the highest is handed out first
+    pub fn next<F: Fn(Move) -> i32>(&mut self, quiet_score: F) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateNoisy;
+                    if let Some(m) = self.hash_move.filter(|m| self.board.is_legal(*m)) {
+                        return Some(m);
+                    }
+                    self.hash_move = None;
+                }
+                Stage::GenerateNoisy => {
+                    self.board
+                        .generate_moves(MoveKind::Noisy, !0, &mut self.moves);
+                    for i in 0..self.moves.len() {
+                        self.scores[i] = noisy_score(self.board, self.moves[i]);
+                    }
+                    self.stage = Stage::WinningCaptures;
+                }
+                Stage::WinningCaptures => match self.pick(self.moves.len()) {
+                    Some(i) if self.scores[i] > LOSING_CAPTURE / 2 => {
+                        self.index += 1;
+                        let m = self.moves[i];
+                        if Some(m) != self.hash_move {
+                            return Some(m);
+                        }
+                    }
+                    _ => {
+                        self.losing_start = self.index;
+                        self.losing_end = self.moves.len();
+                        self.stage = if self.noisy_only {
+                            Stage::LosingCaptures
+                        } else {
+                            Stage::Killers
+                        };
+                    }
+                },
+                Stage::Killers => {
+                    if self.killer_index == self.killers.len() {
+                        self.stage = Stage::GenerateQuiets;
+                        continue;
+                    }
+                    let killer = self.killers[self.killer_index];
+                    self.killer_index += 1;
+                    if let Some(m) = killer.filter(|m| {
+                        (Some(*m) != self.hash_move) && self.board.generates(*m, MoveKind::Quiet)
+                    }) {
+                        return Some(m);
+                    }
+                    self.killers[self.killer_index - 1] = None;
+                }
+                Stage::GenerateQuiets => {
+                    self.index = self.losing_end;
+                    self.board
+                        .generate_moves(MoveKind::Quiet, !0, &mut self.moves);
+                    for i in self.index..self.moves.len() {
+                        self.scores[i] = quiet_score(self.moves[i]);
+                    }
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.pick(self.moves.len()) {
+                    Some(i) => {
+                        self.index += 1;
+                        let m = self.moves[i];
+                        if (Some(m) != self.hash_move) && !self.killers.contains(&Some(m)) {
+                            return Some(m);
+                        }
+                    }
+                    None => {
+                        self.index = self.losing_start;
+                        self.stage = Stage::LosingCaptures;
+                    }
+                },
+                Stage::LosingCaptures => match self.pick(self.losing_end) {
+                    Some(i) => {
+                        self.index += 1;
+                        let m = self.moves[i];
+                        if Some(m) != self.hash_move {
+                            return Some(m);
+                        }
+                    }
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    //Swaps the best scoring move left before end to the front of what is left
+    fn pick(&mut self, end: usize) -> Option<usize> {
+        if self.index >= end {
+            return None;
+        }
+        let mut best = self.index;
+        for i in self.index + 1..end {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+        self.moves.swap(self.index, best);
+        self.scores.swap(self.index, best);
+        Some(self.index)
+    }
+}
+
+//Most valuable victim, least valuable attacker. Taking a defended piece worth less than the piece taking it loses
+//material, those captures wait until after the quiet moves.
+fn noisy_score(board: &Board, m: Move) -> i32 {
+    let score = board.move_score(m);
+    if (m.promotion_piece != PromotionPiece::None) | (m.flags & Move::EN_PASSANT != 0) {
+        return score;
+    }
+    let to = m.to_square as usize;
+    if (piece_value(board, to) >= piece_value(board, m.from_square as usize))
+        || !defended(board, to, !board.white_to_play)
+    {
+        score
+    } else {
+        score + LOSING_CAPTURE
+    }
+}
+
+//Whether a piece of the given side attacks the square
+fn defended(board: &Board, square: usize, white: bool) -> bool {
+    let (pawns, knights, bishops, rooks, queens, kings, pawn_side) = if white {
+        (
+            board.white_pawns,
+            board.white_knights,
+            board.white_bishops,
+            board.white_rooks,
+            board.white_queens,
+            board.white_kings,
+            1,
+        )
+    } else {
+        (
+            board.black_pawns,
+            board.black_knights,
+            board.black_bishops,
+            board.black_rooks,
+            board.black_queens,
+            board.black_kings,
+            0,
+        )
+    };
+    let empty = !board.occupied;
+    //A pawn defends the squares a pawn of the other side standing there would attack
+    (PAWN_ATTACKS[pawn_side][square] & pawns)
+        | (KNIGHT_MOVES[square] & knights)
+        | (KING_MOVES[square] & kings)
+        | (bishop_moves(square, empty) & (bishops | queens))
+        | (rook_moves(square, empty) & (rooks | queens))
+        != 0
+}
+
+//The king only ever captures pieces that aren't defended
+fn piece_value(board: &Board, square: usize) -> i32 {
+    let mask = 1 << square;
+    if mask & (board.white_pawns | board.black_pawns) != 0 {
+        100
+    } else if mask
+        & (board.white_knights | board.black_knights | board.white_bishops | board.black_bishops)
+        != 0
+    {
+        300
+    } else if mask & (board.white_rooks | board.black_rooks) != 0 {
+        500
+    } else if mask & (board.white_queens | board.black_queens) != 0 {
+        900
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Board, Move, MovePicker};
+
+    fn picked(board: &Board, hash_move: Option<&str>, killers: [Option<&str>; 2]) -> Vec<String> {
+        let uci = |m: Option<&str>| m.map(|m| Move::from_uci(m).unwrap());
+        let mut picker = MovePicker::new(board, uci(hash_move), killers.map(uci));
+        let mut res = Vec::new();
+        while let Some(m) = picker.next(|_| 0) {
+            res.push(m.to_uci());
+        }
+        res
+    }
+
+    #[test]
+    fn same_moves_as_generation() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let mut expected: Vec<String> =
+                board.legal_moves().iter().map(|m| m.to_uci()).collect();
+            //A legal hash move and killer come first and only once, illegal ones are dropped
+            let first = expected[expected.len() / 2].clone();
+            let mut moves = picked(&board, Some(&first), [Some("a1a8"), None]);
+            assert_eq!(moves[0], first);
+            moves.sort();
+            expected.sort();
+            assert_eq!(moves, expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn stage_order() {
+        //Pawn takes knight first, then the killer, and the queen taking a defended pawn only after the quiet moves
+        let board = Board::from_fen("4k3/1p6/2p5/5n2/Q3P3/8/8/4K3 w - - 0 1").unwrap();
+        let moves = picked(&board, None, [Some("e1d2"), None]);
+        assert_eq!(&moves[..2], ["e4f5", "e1d2"]);
+        assert_eq!(moves.last().unwrap(), "a4c6");
+        assert_eq!(moves.iter().filter(|m| *m == "e1d2").count(), 1);
+    }
+}
@@ -1,62 +1,568 @@
 use rand::Rng;
-use std::collections::HashMap;
+use std::borrow::Cow;
 use std::fs;
+use std::io;
+use std::path::Path;
 
 use super::{
     board_move::{Move, PromotionPiece},
+    book_builder::BookRecord,
     utils::print_bit_board,
     BitBoard, Board,
 };
 
+//Books compiled into the binary, chosen by file name without the .bin
+pub const BUNDLED_BOOKS: [&str; 7] = [
+    "Elo2400",
+    "gm2600",
+    "Performance",
+    "baron30",
+    "varied",
+    "final-book",
+    //Not laid out as Polyglot records, picking it gives the error from check_length
+    "gavibook-small",
+];
+
+//Used by the engine and the GUI until another book list is given
+pub const DEFAULT_BOOK: &str = "Elo2400";
+
+const RECORD_SIZE: usize = 16;
+
+//Polyglot book read straight from its bytes, bundled books are never copied
+#[derive(Clone)]
 pub struct OpeningBook {
-    pub moves: HashMap<u64, Vec<Move>>,
+    data: Cow<'static, [u8]>,
+    pub policy: BookPolicy,
+    //Half moves into the game after which the book is no longer used
+    pub max_depth: Option<usize>,
 }
 
-impl OpeningBook {
-    pub fn new(bytes: Vec<u8>) -> Self {
-        let mut moves: HashMap<u64, Vec<Move>> = HashMap::new();
-        for i in bytes.chunks(16) {
-            let key = u64::from_be_bytes(i[0..8].try_into().unwrap());
-            let book_move = u16::from_be_bytes(i[8..10].try_into().unwrap());
-            let to_file = book_move & 0x7 >> 0;
-            let to_row = book_move & 0x38 >> 3;
-            let from_file = book_move & 0x1c0 >> 6;
-            let from_row = book_move & 0xe00 >> 9;
-
-            let weight = u16::from_be_bytes(i[10..12].try_into().unwrap());
-            let full_move = Move {
-                to_square: book_move & 0x3f,
-                from_square: (book_move & 0xfc0) >> 6,
-                promotion_piece: PromotionPiece::None,
-                weight: weight,
-            };
-            match moves.get_mut(&key) {
-                Some(value) => value.push(full_move),
-                None => {
-                    let move_vec = vec![full_move];
-                    moves.insert(key, move_vec);
+//How to choose between the legal book moves for a position. Entries with a weight of 0 are never played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BookPolicy {
+    BestWeight,
+    WeightedRandom,
+    Uniform,
+}
+
+impl BookPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "bestweight" => Some(Self::BestWeight),
+            "weightedrandom" => Some(Self::WeightedRandom),
+            "uniform" => Some(Self::Uniform),
+            _ => None,
+        }
+    }
+
+    fn pick(self, moves: &[Move]) -> Option<Move> {
+        match self {
+            Self::BestWeight => {
+                moves
+                    .iter()
+                    .filter(|m| m.weight > 0)
+                    .fold(None, |best: Option<Move>, m| match best {
+                        Some(best) if best.weight >= m.weight => Some(best),
+                        _ => Some(*m),
+                    })
+            }
+            Self::WeightedRandom => pick_random(moves),
+            Self::Uniform => {
+                let playable: Vec<Move> = moves.iter().copied().filter(|m| m.weight > 0).collect();
+                if playable.is_empty() {
+                    None
+                } else {
+                    Some(playable[rand::thread_rng().gen_range(0..playable.len())])
                 }
-            };
+            }
+        }
+    }
+}
+
+pub struct BookLookup {
+    pub book_move: Option<Move>,
+    //Entries for the position that aren't legal in it, from a key collision or a broken book
+    pub dropped: usize,
+}
+
+impl OpeningBook {
+    //Polyglot books are sorted by key, a file that isn't gets sorted here so it can be searched
+    pub fn new(mut bytes: Vec<u8>) -> io::Result<Self> {
+        check_length(&bytes)?;
+        if !is_sorted(&bytes) {
+            let mut records: Vec<&[u8]> = bytes.chunks_exact(RECORD_SIZE).collect();
+            records.sort_by_key(|x| record_key(x));
+            bytes = records.concat();
+        }
+        Ok(OpeningBook::from_data(Cow::Owned(bytes)))
+    }
+
+    //Uses the bytes where they are unless they need sorting first
+    pub fn from_static(bytes: &'static [u8]) -> io::Result<Self> {
+        check_length(bytes)?;
+        if is_sorted(bytes) {
+            Ok(OpeningBook::from_data(Cow::Borrowed(bytes)))
+        } else {
+            OpeningBook::new(bytes.to_vec())
+        }
+    }
+
+    fn from_data(data: Cow<'static, [u8]>) -> Self {
+        OpeningBook {
+            data,
+            policy: BookPolicy::WeightedRandom,
+            max_depth: None,
+        }
+    }
+
+    //Number of records in the book
+    pub fn len(&self) -> usize {
+        self.data.len() / RECORD_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    #[inline]
+    fn record(&self, index: usize) -> &[u8] {
+        &self.data[index * RECORD_SIZE..(index + 1) * RECORD_SIZE]
+    }
+
+    //Every record in the book, in file order
+    pub fn records(&self) -> impl Iterator<Item = BookRecord> + '_ {
+        self.data
+            .chunks_exact(RECORD_SIZE)
+            .map(BookRecord::from_bytes)
+    }
+
+    //Every move the book has for a key, found by binary searching the records
+    pub fn entries(&self, key: u64) -> Vec<Move> {
+        let mut low = 0;
+        let mut high = self.len();
+        while low < high {
+            let middle = (low + high) / 2;
+            if record_key(self.record(middle)) < key {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
         }
-        OpeningBook { moves: moves }
+        (low..self.len())
+            .map(|i| self.record(i))
+            .take_while(|x| record_key(x) == key)
+            .filter_map(decode_record)
+            .collect()
     }
 
-    pub fn get_move(&self, key: u64) -> Option<Move> {
-        match self.moves.get(&key) {
-            Some(value) => Some(pick_random(value)),
-            None => None,
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        OpeningBook::new(fs::read(path)?)
+    }
+
+    pub fn bundled(name: &str) -> Option<io::Result<Self>> {
+        let bytes: &[u8] = match name {
+            "Elo2400" => include_bytes!("../books/Elo2400.bin"),
+            "gm2600" => include_bytes!("../books/gm2600.bin"),
+            "Performance" => include_bytes!("../books/Performance.bin"),
+            "baron30" => include_bytes!("../books/baron30.bin"),
+            "varied" => include_bytes!("../books/varied.bin"),
+            "final-book" => include_bytes!("../books/final-book.bin"),
+            "gavibook-small" => include_bytes!("../books/gavibook-small.bin"),
+            _ => return None,
+        };
+        Some(OpeningBook::from_static(bytes))
+    }
+
+    //A bundled book by name, otherwise a Polyglot file at that path
+    pub fn load(name_or_path: &str) -> io::Result<Self> {
+        match OpeningBook::bundled(name_or_path) {
+            Some(book) => book,
+            None => OpeningBook::from_file(name_or_path),
+        }
+    }
+
+    //Books from a ';' separated list of names or paths, in the same order
+    pub fn load_list(list: &str) -> Result<Vec<Self>, String> {
+        list.split(';')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .map(|name| {
+                OpeningBook::load(name)
+                    .map_err(|err| format!("could not load book {}: {}", name, err))
+            })
+            .collect()
+    }
+
+    //Picks a book move for the position out of the entries that are legal in it
+    pub fn lookup(&self, board: &Board) -> BookLookup {
+        let out_of_book = self
+            .max_depth
+            .is_some_and(|max_depth| board.game_ply() >= max_depth);
+        if out_of_book {
+            return BookLookup {
+                book_move: None,
+                dropped: 0,
+            };
+        }
+        let entries = self.entries(board.zobrist());
+        let legal: Vec<Move> = entries
+            .iter()
+            .filter_map(|m| board.decode_polyglot(*m))
+            .collect();
+        BookLookup {
+            book_move: self.policy.pick(&legal),
+            dropped: entries.len() - legal.len(),
         }
     }
 }
 
-fn pick_random(moves: &Vec<Move>) -> Move {
+impl Board {
+    //Maps a move as Polyglot writes it onto the matching legal move. Castling is stored as the king taking its own
+    //rook, promotions and en passant need no translation once the move is found in the legal move list.
+    pub fn decode_polyglot(&self, book_move: Move) -> Option<Move> {
+        let from_mask: BitBoard = 1 << book_move.from_square;
+        let to_mask: BitBoard = 1 << book_move.to_square;
+        let (kings, rooks) = if self.white_to_play {
+            (self.white_kings, self.white_rooks)
+        } else {
+            (self.black_kings, self.black_rooks)
+        };
+        let to_square = if (from_mask & kings != 0) & (to_mask & rooks & self.castle != 0) {
+            match (book_move.from_square, book_move.to_square) {
+                (4, 7) => 6,
+                (4, 0) => 2,
+                (60, 63) => 62,
+                (60, 56) => 58,
+                _ => return None,
+            }
+        } else {
+            book_move.to_square
+        };
+        let wanted = Move::new(
+            book_move.from_square as usize,
+            to_square as usize,
+            book_move.promotion_piece,
+            Move::QUIET,
+        );
+        self.legal_moves()
+            .into_iter()
+            .find(|m| *m == wanted)
+            .map(|m| Move {
+                weight: book_move.weight,
+                ..m
+            })
+    }
+}
+
+//Anything that isn't a whole number of records isn't a Polyglot book
+fn check_length(bytes: &[u8]) -> io::Result<()> {
+    if bytes.len().is_multiple_of(RECORD_SIZE) {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "not a Polyglot book, {} bytes is not a whole number of {} byte records",
+                bytes.len(),
+                RECORD_SIZE
+            ),
+        ))
+    }
+}
+
+fn is_sorted(bytes: &[u8]) -> bool {
+    bytes
+        .chunks_exact(RECORD_SIZE)
+        .zip(bytes.chunks_exact(RECORD_SIZE).skip(1))
+        .all(|(a, b)| record_key(a) <= record_key(b))
+}
+
+#[inline]
+fn record_key(record: &[u8]) -> u64 {
+    u64::from_be_bytes(record[0..8].try_into().unwrap())
+}
+
+fn decode_record(record: &[u8]) -> Option<Move> {
+    let book_move = u16::from_be_bytes(record[8..10].try_into().unwrap());
+    let weight = u16::from_be_bytes(record[10..12].try_into().unwrap());
+    //Bits 12 to 14 hold the promotion piece, anything out of range is a broken entry
+    let promotion_piece = PromotionPiece::from_num(((book_move >> 12) & 0x7) as u8)?;
+    Some(Move {
+        to_square: book_move & 0x3f,
+        from_square: (book_move & 0xfc0) >> 6,
+        promotion_piece,
+        flags: Move::QUIET,
+        weight,
+    })
+}
+
+//Moves with a weight of 0 are in the book but never played
+fn pick_random(moves: &[Move]) -> Option<Move> {
     let sum = moves.iter().fold(0, |acc, x| acc + x.weight as usize);
+    if sum == 0 {
+        return None;
+    }
     let mut random = rand::thread_rng().gen_range(1..sum + 1);
     for i in moves {
         if random <= i.weight as usize {
-            return i.clone();
+            return Some(*i);
         }
         random -= i.weight as usize;
     }
-    panic!("");
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        evaluation::MaterialEvaluator, history::History, search::SearchLimits,
+        transposition::TranspositionTable,
+    };
+    use super::{
+        record_key, Board, BookPolicy, BookRecord, Move, OpeningBook, PromotionPiece, BUNDLED_BOOKS,
+    };
+
+    fn book_move(from: usize, to: usize, promotion_piece: PromotionPiece) -> Move {
+        Move::new(from, to, promotion_piece, Move::QUIET)
+    }
+
+    //Book with (key, from, to, weight) entries, in the order given
+    fn book_with(entries: &[(u64, u16, u16, u16)]) -> OpeningBook {
+        let bytes: Vec<u8> = entries
+            .iter()
+            .flat_map(|(key, from, to, weight)| {
+                BookRecord {
+                    key: *key,
+                    book_move: to | (from << 6),
+                    weight: *weight,
+                    learn: 0,
+                }
+                .to_bytes()
+            })
+            .collect();
+        OpeningBook::new(bytes).unwrap()
+    }
+
+    #[test]
+    fn binary_search() {
+        let book = book_with(&[
+            (9, 12, 28, 1),
+            (3, 11, 27, 1),
+            (9, 6, 21, 2),
+            (1, 1, 18, 1),
+            (u64::MAX, 52, 36, 1),
+        ]);
+        let squares = |key| -> Vec<(u16, u16)> {
+            book.entries(key)
+                .iter()
+                .map(|m| (m.from_square, m.to_square))
+                .collect()
+        };
+        assert_eq!(squares(9), [(12, 28), (6, 21)]);
+        assert_eq!(squares(3), [(11, 27)]);
+        assert_eq!(squares(1), [(1, 18)]);
+        assert_eq!(squares(u64::MAX), [(52, 36)]);
+        assert!(squares(0).is_empty());
+        assert!(squares(5).is_empty());
+        assert!(OpeningBook::new(Vec::new()).unwrap().entries(9).is_empty());
+    }
+
+    #[test]
+    fn castling_is_king_takes_rook() {
+        let board =
+            Board::from_fen("r3k2r/pppqbppp/2np1n2/4p3/4P3/2NP1N2/PPPQBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let short = board.decode_polyglot(book_move(4, 7, PromotionPiece::None));
+        assert_eq!(short, Some(book_move(4, 6, PromotionPiece::None)));
+        assert_ne!(short.unwrap().flags & Move::CASTLE, 0);
+        let long = board.decode_polyglot(book_move(4, 0, PromotionPiece::None));
+        assert_eq!(long, Some(book_move(4, 2, PromotionPiece::None)));
+
+        let board =
+            Board::from_fen("r3k2r/pppqbppp/2np1n2/4p3/4P3/2NP1N2/PPPQBPPP/R3K2R b KQkq - 0 1")
+                .unwrap();
+        let short = board.decode_polyglot(book_move(60, 63, PromotionPiece::None));
+        assert_eq!(short, Some(book_move(60, 62, PromotionPiece::None)));
+        let long = board.decode_polyglot(book_move(60, 56, PromotionPiece::None));
+        assert_eq!(long, Some(book_move(60, 58, PromotionPiece::None)));
+    }
+
+    #[test]
+    fn promotion_and_en_passant() {
+        let board = Board::from_fen("8/1P6/8/8/8/8/6k1/K7 w - - 0 1").unwrap();
+        let decoded = board.decode_polyglot(book_move(49, 57, PromotionPiece::Knight));
+        assert_eq!(decoded, Some(book_move(49, 57, PromotionPiece::Knight)));
+
+        let board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let decoded = board.decode_polyglot(book_move(36, 43, PromotionPiece::None));
+        assert_ne!(decoded.unwrap().flags & Move::EN_PASSANT, 0);
+    }
+
+    #[test]
+    fn illegal_moves_are_rejected() {
+        let board = Board::default();
+        //No castling through pieces
+        assert_eq!(
+            board.decode_polyglot(book_move(4, 7, PromotionPiece::None)),
+            None
+        );
+        //Not a move at all
+        assert_eq!(
+            board.decode_polyglot(book_move(12, 36, PromotionPiece::None)),
+            None
+        );
+        //Black's move with white to play
+        assert_eq!(
+            board.decode_polyglot(book_move(52, 36, PromotionPiece::None)),
+            None
+        );
+    }
+
+    //Every move the bundled book has for the first few plies should be legal
+    #[test]
+    fn bundled_book_decodes() {
+        let book = OpeningBook::bundled("Elo2400").unwrap().unwrap();
+        let mut positions = vec![Board::default()];
+        for _ in 0..4 {
+            let mut next = Vec::new();
+            for board in positions {
+                for raw in book.entries(board.zobrist()) {
+                    let decoded = board.decode_polyglot(raw);
+                    assert!(decoded.is_some(), "{:?} in {}", raw, board.to_fen());
+                    next.push(board.make_move(decoded.unwrap()));
+                }
+            }
+            positions = next;
+        }
+        assert!(!positions.is_empty());
+    }
+
+    #[test]
+    fn lookup_skips_illegal_entries() {
+        let board = Board::default();
+        let key = board.zobrist();
+        let book = book_with(&[(key, 4, 7, 10), (key, 12, 28, 5), (key, 52, 36, 10)]);
+        let lookup = book.lookup(&board);
+        assert_eq!(
+            lookup.book_move,
+            Some(book_move(12, 28, PromotionPiece::None))
+        );
+        assert_eq!(lookup.dropped, 2);
+
+        let book = book_with(&[(key, 4, 7, 10)]);
+        let lookup = book.lookup(&board);
+        assert_eq!(lookup.book_move, None);
+        assert_eq!(lookup.dropped, 1);
+    }
+
+    #[test]
+    fn bundled_books_load() {
+        for name in BUNDLED_BOOKS.iter().filter(|x| **x != "gavibook-small") {
+            let book = OpeningBook::bundled(name).unwrap().unwrap();
+            assert!(!book.is_empty(), "{}", name);
+            let keys: Vec<u64> = (0..book.len())
+                .map(|i| record_key(book.record(i)))
+                .collect();
+            assert!(
+                keys.windows(2).all(|x| x[0] <= x[1]),
+                "{} is not sorted",
+                name
+            );
+        }
+        let err = OpeningBook::bundled("gavibook-small")
+            .unwrap()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("not a Polyglot book"));
+        assert!(OpeningBook::bundled("codekiddy").is_none());
+        assert!(OpeningBook::load("no/such/book.bin").is_err());
+
+        let books = OpeningBook::load_list(" Elo2400;; gm2600 ").unwrap();
+        assert_eq!(books.len(), 2);
+        assert!(OpeningBook::load_list("").unwrap().is_empty());
+        let err = OpeningBook::load_list("Elo2400;no/such/book.bin").err();
+        assert!(err.unwrap().contains("no/such/book.bin"));
+    }
+
+    #[test]
+    fn partial_records_are_rejected() {
+        let mut bytes = BookRecord {
+            key: 1,
+            book_move: 28 | (12 << 6),
+            weight: 1,
+            learn: 0,
+        }
+        .to_bytes()
+        .to_vec();
+        assert_eq!(OpeningBook::new(bytes.clone()).unwrap().len(), 1);
+        bytes.push(0);
+        let err = OpeningBook::new(bytes).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(OpeningBook::from_static(&[0; 20]).is_err());
+    }
+
+    #[test]
+    fn policies_and_depth() {
+        let board = Board::default();
+        let key = board.zobrist();
+        let mut book = book_with(&[(key, 12, 28, 3), (key, 11, 27, 7), (key, 6, 21, 0)]);
+
+        book.policy = BookPolicy::BestWeight;
+        assert_eq!(
+            book.lookup(&board).book_move,
+            Some(book_move(11, 27, PromotionPiece::None))
+        );
+        //The move with no weight is never picked
+        for policy in [BookPolicy::WeightedRandom, BookPolicy::Uniform] {
+            book.policy = policy;
+            for _ in 0..20 {
+                let picked = book.lookup(&board).book_move.unwrap();
+                assert_ne!(picked, book_move(6, 21, PromotionPiece::None));
+            }
+        }
+
+        book.max_depth = Some(0);
+        assert_eq!(book.lookup(&board).book_move, None);
+        book.max_depth = Some(1);
+        assert!(book.lookup(&board).book_move.is_some());
+    }
+
+    #[test]
+    fn books_are_chained() {
+        let board = Board::default();
+        let key = board.zobrist();
+        let broken = book_with(&[(key, 4, 7, 10)]);
+        let repertoire = book_with(&[(key, 11, 27, 1)]);
+        let fallback = book_with(&[(key, 12, 28, 1)]);
+        let mut find = |books: &[OpeningBook]| {
+            board.find_move(
+                &SearchLimits::depth(1),
+                0,
+                &mut TranspositionTable::new(1),
+                books,
+                &mut History::new(),
+                &mut MaterialEvaluator,
+                |_| (),
+            )
+        };
+
+        let found = find(&[broken.clone(), repertoire.clone(), fallback.clone()]);
+        assert_eq!(
+            found.best_move,
+            Some(book_move(11, 27, PromotionPiece::None))
+        );
+        assert!(found.from_book);
+        assert_eq!(found.book_dropped, 1);
+
+        let found = find(&[fallback, repertoire]);
+        assert_eq!(
+            found.best_move,
+            Some(book_move(12, 28, PromotionPiece::None))
+        );
+
+        let found = find(&[broken]);
+        assert!(!found.from_book);
+        assert!(found.best_move.is_some());
+    }
 }
@@ -0,0 +1,217 @@
+use super::{
+    utils::{north_east_one, north_west_one, south_east_one, south_west_one, BitBoardIter},
+    BitBoard, Board,
+};
+
+const FILE_A: BitBoard = 0x0101010101010101;
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+//Bonuses and penalties as (middlegame, endgame) centipawns, passed pawns by how far they have come
+const PASSED_MIDDLEGAME: [i32; 8] = [0, 0, 5, 10, 20, 35, 60, 0];
+const PASSED_ENDGAME: [i32; 8] = [0, 10, 15, 25, 45, 75, 110, 0];
+const DOUBLED: (i32, i32) = (-10, -20);
+const ISOLATED: (i32, i32) = (-10, -15);
+const BACKWARD: (i32, i32) = (-8, -10);
+//For every pawn defended by another pawn
+const CHAIN: (i32, i32) = (5, 5);
+//For every group of pawns after the first
+const ISLAND: (i32, i32) = (-5, -10);
+
+#[derive(Clone, Copy)]
+struct PawnEntry {
+    key: u64,
+    middlegame: i32,
+    endgame: i32,
+}
+
+//Pawn structure scores by pawn key. Pawns move rarely so most positions in a search share a handful of structures.
+pub struct PawnTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        PawnTable {
+            entries: vec![None; PAWN_TABLE_SIZE],
+        }
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        PawnTable::new()
+    }
+}
+
+impl Board {
+    //(middlegame, endgame) pawn structure score from white's point of view
+    pub fn pawn_structure(&self) -> (i32, i32) {
+        let white = pawn_score(self.white_pawns, self.black_pawns, true);
+        let black = pawn_score(self.black_pawns, self.white_pawns, false);
+        (white.0 - black.0, white.1 - black.1)
+    }
+
+    pub fn cached_pawn_structure(&self, pawn_table: &mut PawnTable) -> (i32, i32) {
+        let key = self.pawn_key();
+        let slot = &mut pawn_table.entries[key as usize & (PAWN_TABLE_SIZE - 1)];
+        if let Some(entry) = slot.filter(|entry| entry.key == key) {
+            return (entry.middlegame, entry.endgame);
+        }
+        let (middlegame, endgame) = self.pawn_structure();
+        *slot = Some(PawnEntry {
+            key,
+            middlegame,
+            endgame,
+        });
+        (middlegame, endgame)
+    }
+}
+
+fn pawn_score(own: BitBoard, enemy: BitBoard, white: bool) -> (i32, i32) {
+    let (own_attacks, enemy_attacks) = if white {
+        (
+            north_east_one(own) | north_west_one(own),
+            south_east_one(enemy) | south_west_one(enemy),
+        )
+    } else {
+        (
+            south_east_one(own) | south_west_one(own),
+            north_east_one(enemy) | north_west_one(enemy),
+        )
+    };
+    let mut middlegame = 0;
+    let mut endgame = 0;
+    let mut add = |term: (i32, i32)| {
+        middlegame += term.0;
+        endgame += term.1;
+    };
+
+    for square in BitBoardIter(own) {
+        let file = FILE_A << (square % 8);
+        let neighbours = adjacent_files(square % 8);
+        let ahead = ranks_ahead(square, white);
+        let doubled = own & file & ahead != 0;
+        if doubled {
+            add(DOUBLED);
+        }
+        //The pawn in front of a doubled pair is the one that counts as passed
+        if !doubled & (enemy & (file | neighbours) & ahead == 0) {
+            let rank = if white { square / 8 } else { 7 - square / 8 };
+            add((PASSED_MIDDLEGAME[rank], PASSED_ENDGAME[rank]));
+        }
+        if own & neighbours == 0 {
+            add(ISOLATED);
+        } else {
+            //No pawn beside or behind it can come up to defend it and it can't safely step forward
+            let stop = if white { square + 8 } else { square - 8 };
+            if (own & neighbours & !ahead == 0) & (enemy_attacks & (1 << stop) != 0) {
+                add(BACKWARD);
+            }
+        }
+        if own_attacks & (1 << square) != 0 {
+            add(CHAIN);
+        }
+    }
+
+    let files = (0..8)
+        .filter(|file| own & (FILE_A << file) != 0)
+        .fold(0u8, |files, file| files | (1 << file));
+    let islands = (files & !(files << 1)).count_ones() as i32;
+    if islands > 1 {
+        add((ISLAND.0 * (islands - 1), ISLAND.1 * (islands - 1)));
+    }
+    (middlegame, endgame)
+}
+
+fn adjacent_files(file: usize) -> BitBoard {
+    let mut res = 0;
+    if file > 0 {
+        res |= FILE_A << (file - 1);
+    }
+    if file < 7 {
+        res |= FILE_A << (file + 1);
+    }
+    res
+}
+
+//Every rank in front of the square from the side of the pawn on it
+fn ranks_ahead(square: usize, white: bool) -> BitBoard {
+    let rank = square / 8;
+    if white {
+        if rank == 7 {
+            0
+        } else {
+            !0 << ((rank + 1) * 8)
+        }
+    } else {
+        (1 << (rank * 8)) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pawn_score, Board, PawnTable, BACKWARD, CHAIN, DOUBLED, ISLAND, ISOLATED, PASSED_ENDGAME,
+        PASSED_MIDDLEGAME,
+    };
+
+    //Score of white's pawns alone, black's aren't taken off
+    fn white_score(fen: &str) -> (i32, i32) {
+        let board = Board::from_fen(fen).unwrap();
+        pawn_score(board.white_pawns, board.black_pawns, true)
+    }
+
+    #[test]
+    fn recognises_structures() {
+        //A lone pawn is isolated and, with nothing in its way, passed
+        assert_eq!(
+            white_score("4k3/8/8/8/3P4/8/8/4K3 w - - 0 1"),
+            (
+                PASSED_MIDDLEGAME[3] + ISOLATED.0,
+                PASSED_ENDGAME[3] + ISOLATED.1
+            )
+        );
+        //Blocked on its file, so neither passed nor anything else
+        assert_eq!(white_score("4k3/8/3p4/8/3P4/8/8/4K3 w - - 0 1"), ISOLATED);
+        //Two doubled isolated pawns, only the front one passed
+        assert_eq!(
+            white_score("4k3/8/8/3P4/3P4/8/8/4K3 w - - 0 1"),
+            (
+                PASSED_MIDDLEGAME[4] + DOUBLED.0 + 2 * ISOLATED.0,
+                PASSED_ENDGAME[4] + DOUBLED.1 + 2 * ISOLATED.1
+            )
+        );
+        //d3 defends c4 but nothing can come up to defend it, and e5 takes its stop square
+        assert_eq!(
+            white_score("4k3/8/1p6/4p3/2P5/3P4/8/4K3 w - - 0 1"),
+            (BACKWARD.0 + CHAIN.0, BACKWARD.1 + CHAIN.1)
+        );
+        //b3 defends c4, which is past every black pawn
+        assert_eq!(
+            white_score("4k3/p6p/8/8/2P5/1P6/8/4K3 w - - 0 1"),
+            (CHAIN.0 + PASSED_MIDDLEGAME[3], CHAIN.1 + PASSED_ENDGAME[3])
+        );
+        //Pawns on the a and h files are two islands
+        assert_eq!(
+            white_score("4k3/1p4p1/8/8/8/8/P6P/4K3 w - - 0 1"),
+            (2 * ISOLATED.0 + ISLAND.0, 2 * ISOLATED.1 + ISLAND.1)
+        );
+    }
+
+    #[test]
+    fn cache_matches() {
+        let mut table = PawnTable::new();
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(
+                board.cached_pawn_structure(&mut table),
+                board.pawn_structure()
+            );
+        }
+        assert_eq!(Board::default().pawn_structure(), (0, 0));
+    }
+}
@@ -0,0 +1,163 @@
+use super::{board_move::Move, Board};
+
+impl Board {
+    //Counts the leaf nodes of the legal move tree, the standard way of checking move generation
+    pub fn perft(&self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|m| self.make_move(m).perft(depth - 1))
+            .sum()
+    }
+
+    //Perft split by root move, for hunting down which branch disagrees with a reference engine
+    pub fn divide(&self, depth: usize) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        self.legal_moves()
+            .into_iter()
+            .map(|m| (m, self.make_move(m).perft(depth - 1)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Board;
+
+    fn check(fen: &str, expected: &[u64]) {
+        let board = Board::from_fen(fen).unwrap();
+        for (depth, nodes) in expected.iter().enumerate() {
+            assert_eq!(
+                board.perft(depth + 1),
+                *nodes,
+                "perft {} of {}",
+                depth + 1,
+                fen
+            );
+        }
+    }
+
+    fn perft(fen: &str, depth: usize) -> u64 {
+        Board::from_fen(fen).unwrap().perft(depth)
+    }
+
+    //Walks the tree through the board successor generator instead of the move list
+    fn successor_perft(board: Board, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        board
+            .successors()
+            .into_iter()
+            .map(|(_, next)| successor_perft(next, depth - 1))
+            .sum()
+    }
+
+    #[test]
+    fn start_position() {
+        check(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[20, 400, 8902, 197281],
+        );
+    }
+
+    #[test]
+    fn kiwipete() {
+        check(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862],
+        );
+    }
+
+    #[test]
+    fn rook_endgame() {
+        check(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2812, 43238, 674624],
+        );
+    }
+
+    #[test]
+    fn promotions_and_castling() {
+        check(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467, 422333],
+        );
+        check(
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            &[6, 264, 9467, 422333],
+        );
+    }
+
+    #[test]
+    fn promotion_with_discovered_check() {
+        check(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        );
+    }
+
+    #[test]
+    fn middlegame() {
+        check(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            &[46, 2079, 89890],
+        );
+    }
+
+    #[test]
+    fn en_passant() {
+        //Taking en passant would leave the king in check along the rank
+        assert_eq!(perft("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", 6), 1134888);
+        //Taking en passant gives check
+        assert_eq!(perft("8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1", 6), 1015133);
+        assert_eq!(perft("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 6), 1440467);
+        //Taking en passant is the only way out of check
+        assert_eq!(perft("8/8/8/2k5/2pP4/8/B7/4K3 b - d3 0 3", 1), 8);
+    }
+
+    #[test]
+    fn castling() {
+        assert_eq!(perft("5k2/8/8/8/8/8/8/4K2R w K - 0 1", 6), 661072);
+        assert_eq!(perft("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", 6), 803711);
+        assert_eq!(
+            perft("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", 4),
+            1274206
+        );
+        assert_eq!(
+            perft("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", 4),
+            1720476
+        );
+    }
+
+    #[test]
+    fn promotion() {
+        assert_eq!(perft("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", 6), 3821001);
+        assert_eq!(perft("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", 6), 217342);
+        assert_eq!(perft("8/P1k5/K7/8/8/8/8/8 w - - 0 1", 6), 92683);
+    }
+
+    #[test]
+    fn checks_and_stalemate() {
+        assert_eq!(perft("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", 5), 1004658);
+        assert_eq!(perft("K1k5/8/P7/8/8/8/8/8 w - - 0 1", 6), 2217);
+        assert_eq!(perft("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", 7), 567584);
+        assert_eq!(perft("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", 4), 23527);
+    }
+
+    #[test]
+    fn successors_match_move_list() {
+        let board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        assert_eq!(successor_perft(board, 3), board.perft(3));
+    }
+}
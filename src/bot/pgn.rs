@@ -0,0 +1,262 @@
+use super::{
+    board_move::{Move, PromotionPiece},
+    utils::parse_square,
+    BitBoard, Board,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+}
+
+impl GameResult {
+    fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "1-0" => Some(Self::WhiteWin),
+            "0-1" => Some(Self::BlackWin),
+            "1/2-1/2" => Some(Self::Draw),
+            _ => None,
+        }
+    }
+}
+
+//The main line of a game as it is written, moves are only checked when the game is replayed
+#[derive(Default)]
+pub struct PgnGame {
+    pub start: Option<String>,
+    pub moves: Vec<String>,
+    //None for games that were never finished
+    pub result: Option<GameResult>,
+}
+
+impl PgnGame {
+    //Plays the moves from the starting position, stopping at the first one that can't be read or isn't legal
+    pub fn replay(&self) -> (Vec<(Board, Move)>, bool) {
+        let mut board = match &self.start {
+            Some(fen) => match Board::from_fen(fen) {
+                Ok(board) => board,
+                Err(_) => return (Vec::new(), false),
+            },
+            None => Board::default(),
+        };
+        let mut res = Vec::with_capacity(self.moves.len());
+        for san in &self.moves {
+            match board.parse_san(san) {
+                Some(m) => {
+                    res.push((board, m));
+                    board = board.make_move(m);
+                }
+                None => return (res, false),
+            }
+        }
+        (res, true)
+    }
+}
+
+//Reads every game in a PGN file. Comments, variations and annotation glyphs are skipped.
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::default();
+    let mut in_moves = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                //A tag after moves have been read starts the next game
+                if in_moves {
+                    games.push(std::mem::take(&mut game));
+                    in_moves = false;
+                }
+                let tag: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                read_tag(&mut game, &tag);
+            }
+            '{' => chars.by_ref().take_while(|c| *c != '}').for_each(drop),
+            ';' => chars.by_ref().take_while(|c| *c != '\n').for_each(drop),
+            '(' => {
+                let mut depth = 1;
+                for c in chars.by_ref() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => (),
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                let mut token = c.to_string();
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || "[{;(".contains(*next) {
+                        break;
+                    }
+                    token.push(*next);
+                    chars.next();
+                }
+                in_moves = true;
+                if (token == "*") | GameResult::from_token(&token).is_some() {
+                    game.result = game.result.or(GameResult::from_token(&token));
+                    games.push(std::mem::take(&mut game));
+                    in_moves = false;
+                    continue;
+                }
+                //Move numbers can be written against the move, e.g. 12.e4 or 12...e5
+                let san = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                if !san.is_empty() && !san.starts_with('$') {
+                    game.moves.push(san.to_string());
+                }
+            }
+        }
+    }
+    if in_moves {
+        games.push(game);
+    }
+    games
+}
+
+fn read_tag(game: &mut PgnGame, tag: &str) {
+    let (name, value) = match tag.split_once(' ') {
+        Some((name, value)) => (name, value.trim().trim_matches('"')),
+        None => return,
+    };
+    match name {
+        "Result" => game.result = GameResult::from_token(value),
+        "FEN" => game.start = Some(value.to_string()),
+        _ => (),
+    }
+}
+
+impl Board {
+    //Finds the legal move written in standard algebraic notation, e.g. Nbd7, exd6, e8=N+ or O-O-O
+    pub fn parse_san(&self, san: &str) -> Option<Move> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let moves = self.legal_moves();
+        let castle = match san {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        if let Some(file) = castle {
+            return moves
+                .into_iter()
+                .find(|m| (m.flags & Move::CASTLE != 0) & (m.to_square % 8 == file));
+        }
+
+        let (san, promotion_piece) = match san.char_indices().last()? {
+            (i, 'N') if i > 1 => (&san[..i], PromotionPiece::Knight),
+            (i, 'B') if i > 1 => (&san[..i], PromotionPiece::Bishop),
+            (i, 'R') if i > 1 => (&san[..i], PromotionPiece::Rook),
+            (i, 'Q') if i > 1 => (&san[..i], PromotionPiece::Queen),
+            _ => (san, PromotionPiece::None),
+        };
+        let san = san.trim_end_matches('=');
+        if san.len() < 2 || !san.is_ascii() {
+            return None;
+        }
+        let to = parse_square(&san[san.len() - 2..])?;
+        let (pieces, rest) = self.san_pieces(san);
+        let rest = &rest[..rest.len() - 2];
+
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in rest.chars() {
+            match c {
+                'a'..='h' => from_file = Some(c as u16 - 'a' as u16),
+                '1'..='8' => from_rank = Some(c as u16 - '1' as u16),
+                'x' | '-' | ':' => (),
+                _ => return None,
+            }
+        }
+        moves.into_iter().find(|m| {
+            (m.to_square as usize == to)
+                & (pieces & (1 << m.from_square) != 0)
+                & from_file.is_none_or(|file| m.from_square % 8 == file)
+                & from_rank.is_none_or(|rank| m.from_square / 8 == rank)
+                //Some files leave out the piece on a promotion, a queen is the only sensible reading
+                & ((m.promotion_piece == promotion_piece)
+                    | ((promotion_piece == PromotionPiece::None)
+                        & (m.promotion_piece == PromotionPiece::Queen)))
+        })
+    }
+
+    //Pieces of the side to move that the leading letter of a SAN move names, and the move without it
+    fn san_pieces<'a>(&self, san: &'a str) -> (BitBoard, &'a str) {
+        let white = self.white_to_play;
+        let pick = |w: BitBoard, b: BitBoard| if white { w } else { b };
+        match san.chars().next() {
+            Some('N') => (pick(self.white_knights, self.black_knights), &san[1..]),
+            Some('B') => (pick(self.white_bishops, self.black_bishops), &san[1..]),
+            Some('R') => (pick(self.white_rooks, self.black_rooks), &san[1..]),
+            Some('Q') => (pick(self.white_queens, self.black_queens), &san[1..]),
+            Some('K') => (pick(self.white_kings, self.black_kings), &san[1..]),
+            _ => (pick(self.white_pawns, self.black_pawns), san),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_pgn, Board, GameResult};
+
+    fn san(fen: &str, san: &str) -> Option<String> {
+        Board::from_fen(fen)
+            .unwrap()
+            .parse_san(san)
+            .map(|m| m.to_uci())
+    }
+
+    #[test]
+    fn reads_san() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(start, "e4").as_deref(), Some("e2e4"));
+        assert_eq!(san(start, "Nf3").as_deref(), Some("g1f3"));
+        assert_eq!(san(start, "e5"), None);
+        assert_eq!(san(start, "O-O"), None);
+
+        let fen = "r3k2r/pppqbppp/2np1n2/4p3/4P3/2NP1N2/PPPQBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(san(fen, "O-O").as_deref(), Some("e1g1"));
+        assert_eq!(san(fen, "O-O-O+").as_deref(), Some("e1c1"));
+        assert_eq!(san(fen, "Rb1").as_deref(), Some("a1b1"));
+        assert_eq!(san(fen, "Rhf1").as_deref(), Some("h1f1"));
+        assert_eq!(san(fen, "Ng5!?").as_deref(), Some("f3g5"));
+
+        let fen = "4k3/1P6/8/3pP3/8/8/8/R3K2R w - d6 0 1";
+        assert_eq!(san(fen, "exd6").as_deref(), Some("e5d6"));
+        assert_eq!(san(fen, "b8=N+").as_deref(), Some("b7b8n"));
+        assert_eq!(san(fen, "b8Q").as_deref(), Some("b7b8q"));
+        assert_eq!(san(fen, "b8").as_deref(), Some("b7b8q"));
+    }
+
+    #[test]
+    fn reads_games() {
+        let text = r#"[Event "Test"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {main line} Nc6 (2... d6 3. d4) 3. Bb5 $1 a6 1-0
+
+[Event "Test"]
+[Result "1/2-1/2"]
+
+1.d4 d5 2.c4 ; queen's gambit
+e6 1/2-1/2
+
+1. e4 c5 *
+"#;
+        let games = parse_pgn(text);
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].moves, ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+        assert_eq!(games[0].result, Some(GameResult::WhiteWin));
+        assert_eq!(games[1].moves, ["d4", "d5", "c4", "e6"]);
+        assert_eq!(games[1].result, Some(GameResult::Draw));
+        assert_eq!(games[2].result, None);
+
+        let (moves, complete) = games[0].replay();
+        assert!(complete);
+        assert_eq!(moves.len(), 6);
+        assert_eq!(moves[4].1.to_uci(), "f1b5");
+    }
+}
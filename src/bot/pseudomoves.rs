@@ -122,7 +122,7 @@ pub const KNIGHT_MOVES: [BitBoard; 64] = {
     res
 };
 
-pub const PATH_BETWEEN: [[BitBoard; 64]; 64] = {
+pub static PATH_BETWEEN: [[BitBoard; 64]; 64] = {
     let mut res: [[BitBoard; 64]; 64] = [[0; 64]; 64];
     let mut from: BitBoard = 0;
     while from < 64 {
@@ -156,9 +156,11 @@ const fn path_between(from: BitBoard, to: BitBoard) -> BitBoard {
     }
 }
 
+//The ray scans below fill the magic tables, move generation looks attacks up in magic_bitboards instead
+
 //Source: https://rhysre.net/fast-chess-move-generation-with-magic-bitboards.html
 #[inline]
-pub const fn bishop_moves(square: usize, empty: BitBoard) -> BitBoard {
+pub const fn ray_bishop_moves(square: usize, empty: BitBoard) -> BitBoard {
     let blockers = !empty;
     let mut attacks: BitBoard = 0;
 
@@ -194,7 +196,7 @@ pub const fn bishop_moves(square: usize, empty: BitBoard) -> BitBoard {
 
 //Source: https://rhysre.net/fast-chess-move-generation-with-magic-bitboards.html
 #[inline]
-pub const fn rook_moves(square: usize, empty: BitBoard) -> BitBoard {
+pub const fn ray_rook_moves(square: usize, empty: BitBoard) -> BitBoard {
     let blockers = !empty;
     let mut attacks: BitBoard = 0;
 
@@ -230,7 +232,7 @@ pub const fn rook_moves(square: usize, empty: BitBoard) -> BitBoard {
 }
 
 #[inline]
-pub fn rook_xray(square: usize, empty: BitBoard) -> BitBoard {
+pub const fn ray_rook_xray(square: usize, empty: BitBoard) -> BitBoard {
     let mut blockers = !empty;
     let mut attacks: BitBoard = 0;
 
@@ -260,7 +262,7 @@ pub fn rook_xray(square: usize, empty: BitBoard) -> BitBoard {
     attacks |= RAYS[4][square];
     if RAYS[4][square] & blockers != 0 {
         let blocker_index = BitBoard::leading_zeros(RAYS[4][square] & blockers) as usize;
-        blockers &= !((1 as BitBoard) << 63 - blocker_index);
+        blockers &= !((1 as BitBoard) << (63 - blocker_index));
         if RAYS[4][square] & blockers != 0 {
             let blocker_index = BitBoard::leading_zeros(RAYS[4][square] & blockers) as usize;
             attacks &= !RAYS[4][63 - blocker_index];
@@ -271,7 +273,7 @@ pub fn rook_xray(square: usize, empty: BitBoard) -> BitBoard {
     attacks |= RAYS[6][square];
     if RAYS[6][square] & blockers != 0 {
         let blocker_index = BitBoard::leading_zeros(RAYS[6][square] & blockers) as usize;
-        blockers &= !((1 as BitBoard) << 63 - blocker_index);
+        blockers &= !((1 as BitBoard) << (63 - blocker_index));
         if RAYS[6][square] & blockers != 0 {
             let blocker_index = BitBoard::leading_zeros(RAYS[6][square] & blockers) as usize;
             attacks &= !RAYS[6][63 - blocker_index];
@@ -282,7 +284,7 @@ pub fn rook_xray(square: usize, empty: BitBoard) -> BitBoard {
 }
 
 #[inline]
-pub fn bishop_xray(square: usize, empty: BitBoard) -> BitBoard {
+pub const fn ray_bishop_xray(square: usize, empty: BitBoard) -> BitBoard {
     let mut blockers = !empty;
     let mut attacks: BitBoard = 0;
 
@@ -313,7 +315,7 @@ pub fn bishop_xray(square: usize, empty: BitBoard) -> BitBoard {
     attacks |= RAYS[3][square];
     if RAYS[3][square] & blockers != 0 {
         let blocker_index = BitBoard::leading_zeros(RAYS[3][square] & blockers) as usize;
-        blockers &= !((1 as BitBoard) << 63 - blocker_index);
+        blockers &= !((1 as BitBoard) << (63 - blocker_index));
         if RAYS[3][square] & blockers != 0 {
             let blocker_index = BitBoard::leading_zeros(RAYS[3][square] & blockers) as usize;
             attacks &= !RAYS[3][63 - blocker_index];
@@ -324,7 +326,7 @@ pub fn bishop_xray(square: usize, empty: BitBoard) -> BitBoard {
     attacks |= RAYS[5][square];
     if RAYS[5][square] & blockers != 0 {
         let blocker_index = BitBoard::leading_zeros(RAYS[5][square] & blockers) as usize;
-        blockers &= !((1 as BitBoard) << 63 - blocker_index);
+        blockers &= !((1 as BitBoard) << (63 - blocker_index));
         if RAYS[5][square] & blockers != 0 {
             let blocker_index = BitBoard::leading_zeros(RAYS[5][square] & blockers) as usize;
             attacks &= !RAYS[5][63 - blocker_index];
@@ -333,13 +335,3 @@ pub fn bishop_xray(square: usize, empty: BitBoard) -> BitBoard {
 
     attacks
 }
-
-#[inline]
-pub fn queen_xray(square: usize, empty: BitBoard) -> BitBoard {
-    rook_xray(square, empty) | bishop_xray(square, empty)
-}
-
-#[inline]
-pub fn queen_moves(square: usize, empty: BitBoard) -> BitBoard {
-    rook_moves(square, empty) | bishop_moves(square, empty)
-}
@@ -1,235 +1,748 @@
-use std::{collections::HashMap, f32::INFINITY};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use super::{Board, TransEntry, CHECKMATE_THRESHOLD, CHECKMATE_VALUE};
+use super::{
+    board_move::{Move, MoveList},
+    evaluation::Evaluator,
+    heuristics::{is_quiet, Heuristics},
+    history::History,
+    move_picker::MovePicker,
+    transposition::{TransEntry, TranspositionTable},
+    Board,
+};
+
+const MAX_DEPTH: usize = 64;
+//Scores are centipawns from white's point of view. A side that is mated scores MATE less the number of half moves
+//from the root it happens at, so a quicker mate is always preferred.
+pub const MATE: i32 = 32000;
+//Anything further from zero than this is a mate score
+pub const MATE_BOUND: i32 = MATE - MAX_DEPTH as i32;
+//Larger than any real score, for the search window and bounds that are not known
+pub const INFINITE: i32 = MATE + 1;
+//Time kept back for the GUI and the OS so the engine doesn't flag when the clock is nearly empty
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+//How often the clock and the stop flag are looked at, in nodes. Must be a power of two
+const CHECK_INTERVAL: u64 = 1024;
+//How often an infinite search that has nothing left to search looks at the stop flag
+const STOP_POLL: Duration = Duration::from_millis(5);
+
+//When to give up on searching. No new iteration is started after the soft deadline, the search in progress is
+//abandoned at the hard deadline or when stop is set.
+#[derive(Clone)]
+pub struct SearchLimits {
+    pub depth: usize,
+    pub soft_deadline: Option<Instant>,
+    pub hard_deadline: Option<Instant>,
+    pub stop: Arc<AtomicBool>,
+    //Searches until stop is set, whatever it finds, as UCI wants for go infinite
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: usize) -> Self {
+        SearchLimits {
+            depth,
+            soft_deadline: None,
+            hard_deadline: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            infinite: false,
+        }
+    }
+
+    pub fn infinite() -> Self {
+        SearchLimits {
+            infinite: true,
+            ..SearchLimits::depth(MAX_DEPTH)
+        }
+    }
+
+    pub fn move_time(move_time: Duration) -> Self {
+        let deadline = Instant::now() + move_time.saturating_sub(MOVE_OVERHEAD);
+        SearchLimits {
+            depth: MAX_DEPTH,
+            soft_deadline: Some(deadline),
+            hard_deadline: Some(deadline),
+            stop: Arc::new(AtomicBool::new(false)),
+            infinite: false,
+        }
+    }
+
+    //Splits what is left on the clock evenly over the moves to go, with room to overrun on a hard position
+    pub fn clock(time_left: Duration, increment: Duration, moves_to_go: Option<u32>) -> Self {
+        let now = Instant::now();
+        let available = time_left.saturating_sub(MOVE_OVERHEAD);
+        let moves_to_go = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let soft = (available / moves_to_go + increment * 3 / 4).min(available);
+        let hard = (soft * 4).min(available / 2).max(soft);
+        SearchLimits {
+            depth: MAX_DEPTH,
+            soft_deadline: Some(now + soft),
+            hard_deadline: Some(now + hard),
+            stop: Arc::new(AtomicBool::new(false)),
+            infinite: false,
+        }
+    }
+}
+
+//What one iteration of the search found
+#[derive(Clone, Debug, Default)]
+pub struct SearchInfo {
+    pub depth: usize,
+    pub score: i32,
+    //Counted from the start of the search, not just this iteration
+    pub nodes: u64,
+    pub time: Duration,
+    //The moves both sides are expected to play, starting with the move to make
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn best_move(&self) -> Option<Move> {
+        self.pv.first().copied()
+    }
+
+    pub fn nps(&self) -> u64 {
+        let micros = self.time.as_micros().max(1);
+        (self.nodes as u128 * 1_000_000 / micros) as u64
+    }
+}
 
 impl Board {
-    pub fn iterative_search(
+    //Searches one ply deeper each iteration until the limits run out. Only completed iterations count, a search cut
+    //off by the clock is thrown away. report is called with every iteration that completes.
+    pub fn iterative_search<E: Evaluator, R: FnMut(&SearchInfo)>(
         &self,
-        depth: usize,
+        limits: &SearchLimits,
         age: usize,
-        trans_table: &mut HashMap<Board, TransEntry>,
-    ) -> (Board, f32) {
-        for i in 1..depth {
-            self.start_search(depth, age, trans_table);
+        trans_table: &mut TranspositionTable,
+        history: &mut History,
+        evaluator: &mut E,
+        mut report: R,
+    ) -> SearchInfo {
+        let start = Instant::now();
+        let mut searcher = Searcher::new(trans_table, evaluator, history, age, limits);
+        let mut best = SearchInfo::default();
+        let max_depth = if limits.infinite {
+            MAX_DEPTH
+        } else {
+            limits.depth.clamp(1, MAX_DEPTH)
+        };
+        for depth in 1..=max_depth {
+            let score = searcher.search_root(*self, depth);
+            if searcher.aborted {
+                break;
+            }
+            best = SearchInfo {
+                depth,
+                score,
+                nodes: searcher.nodes,
+                time: start.elapsed(),
+                pv: searcher.pv[0].clone(),
+            };
+            report(&best);
+            //Nothing to choose between when there is one move or none
+            if !limits.infinite && (self.legal_moves().len() < 2) {
+                break;
+            }
+            if let Some(deadline) = limits.soft_deadline {
+                if Instant::now() >= deadline {
+                    break;
+                }
+            }
+            searcher.can_abort = true;
+        }
+        //Out of depth before the GUI said stop, the answer has to wait until it does
+        if limits.infinite {
+            while !limits.stop.load(Ordering::Relaxed) {
+                thread::sleep(STOP_POLL);
+            }
         }
-        self.start_search(depth, age, trans_table)
+        best
     }
+}
 
-    pub fn start_search(
-        self,
-        depth: usize,
+struct Searcher<'a, E: Evaluator> {
+    trans_table: &'a mut TranspositionTable,
+    evaluator: &'a mut E,
+    //Positions from the start of the game up to the node being searched
+    history: &'a mut History,
+    age: usize,
+    limits: &'a SearchLimits,
+    nodes: u64,
+    //The first iteration always runs to completion so there is a move to play
+    can_abort: bool,
+    aborted: bool,
+    //Triangular table, the line at each ply is the best line found from the node being searched there
+    pv: Vec<Vec<Move>>,
+    //Killers, history and countermoves, kept from one iteration to the next
+    heuristics: Heuristics,
+    //Moves from the root to the node being searched
+    played: Vec<Move>,
+}
+
+impl<'a, E: Evaluator> Searcher<'a, E> {
+    fn new(
+        trans_table: &'a mut TranspositionTable,
+        evaluator: &'a mut E,
+        history: &'a mut History,
         age: usize,
-        trans_table: &mut HashMap<Board, TransEntry>,
-    ) -> (Board, f32) {
-        if self.white_to_play {
-            Board::alpha_beta(
-                self,
-                depth,
-                -CHECKMATE_VALUE - 2.,
-                CHECKMATE_VALUE + 2.,
-                true,
-                age,
-                trans_table,
-            )
-        } else {
-            Board::alpha_beta(
-                self,
-                depth,
-                -CHECKMATE_VALUE - 2.,
-                CHECKMATE_VALUE + 2.,
-                false,
-                age,
-                trans_table,
-            )
+        limits: &'a SearchLimits,
+    ) -> Self {
+        Searcher {
+            trans_table,
+            evaluator,
+            history,
+            age,
+            limits,
+            nodes: 0,
+            can_abort: false,
+            aborted: false,
+            pv: vec![Vec::new(); MAX_DEPTH + 1],
+            heuristics: Heuristics::new(MAX_DEPTH),
+            played: Vec::with_capacity(MAX_DEPTH),
         }
     }
 
+    //Score of the root, the move to play is the first move of the line at ply 0
+    fn search_root(&mut self, board: Board, depth: usize) -> i32 {
+        //The root is never scored as a repetition, there has to be a move to play
+        self.history.push(board.zobrist());
+        let hash_move = self.root_hash_move(&board);
+        self.pv[0].clear();
+        let (_, score) = self.search_moves(
+            board,
+            depth,
+            0,
+            -INFINITE,
+            INFINITE,
+            board.white_to_play,
+            hash_move,
+        );
+        self.history.pop();
+        score
+    }
+
+    //The best move of the last iteration is searched first, the window it sets makes the rest of the moves cheap
+    fn root_hash_move(&self, board: &Board) -> Option<Move> {
+        self.pv[0].first().copied().or_else(|| {
+            self.trans_table
+                .get(board.zobrist())
+                .map(|entry| entry.response)
+        })
+    }
+
+    //Makes the move followed by the line of the node below it the best line at this ply
+    fn update_pv(&mut self, ply: usize, best_move: Move) {
+        let (lines, below) = self.pv.split_at_mut(ply + 1);
+        let line = &mut lines[ply];
+        line.clear();
+        line.push(best_move);
+        line.extend_from_slice(&below[0]);
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if !self.can_abort {
+            return false;
+        }
+        if self.limits.stop.load(Ordering::Relaxed) {
+            self.aborted = true;
+        }
+        if let Some(deadline) = self.limits.hard_deadline {
+            if Instant::now() >= deadline {
+                self.aborted = true;
+            }
+        }
+        self.aborted
+    }
+
     fn alpha_beta(
+        &mut self,
         board: Board,
         depth: usize,
-        mut alpha: f32,
-        mut beta: f32,
+        ply: usize,
+        mut alpha: i32,
+        mut beta: i32,
         white: bool,
-        age: usize,
-        trans_table: &mut HashMap<Board, TransEntry>,
-    ) -> (Board, f32) {
-        //println!("{}", board.to_fen());
+    ) -> (Option<Move>, i32) {
+        self.nodes += 1;
+        //Nodes that return without searching their moves have no line
+        self.pv[ply].clear();
+        if (self.nodes & (CHECK_INTERVAL - 1) == 0) && self.should_stop() {
+            return (None, 0);
+        }
 
-        let lookup = trans_table.get(&board);
+        let key = board.zobrist();
+        if self.history.is_repetition(key, board.halfmove_clock) {
+            return (None, 0);
+        }
+        //Checkmate on the hundredth half move still counts
+        if (board.halfmove_clock >= 100) && !board.legal_moves().is_empty() {
+            return (None, 0);
+        }
+
+        let lookup = self.trans_table.get(key);
         match lookup {
-            None => (),
-            Some(result) => {
-                if result.depth >= depth {
-                    if result.lower_bound >= beta {
-                        return (result.response, result.lower_bound);
-                    }
-                    if result.upper_bound <= alpha {
-                        return (result.response, result.upper_bound);
-                    };
-                    alpha = f32::max(alpha, result.lower_bound);
-                    beta = f32::min(beta, result.upper_bound);
+            Some(result) if result.depth >= depth => {
+                let lower_bound = score_from_table(result.lower_bound, ply);
+                let upper_bound = score_from_table(result.upper_bound, ply);
+                if lower_bound >= beta {
+                    return (Some(result.response), lower_bound);
                 }
+                if upper_bound <= alpha {
+                    return (Some(result.response), upper_bound);
+                };
+                alpha = alpha.max(lower_bound);
+                beta = beta.min(upper_bound);
             }
+            _ => (),
         }
         if depth == 0 {
-            return (board, board.hueristic());
+            return (None, self.quiescence(board, alpha, beta, white));
         }
-        let mut moves = if white {
-            board.white_moves()
-        } else {
-            board.black_moves()
-        };
+        //The position this node is searching for counts towards repetitions further down the tree
+        self.history.push(key);
+        //An entry too shallow to score the node still has the move that was best last time, which is tried first
+        let hash_move = lookup.map(|entry| entry.response);
+        let result = self.search_moves(board, depth, ply, alpha, beta, white, hash_move);
+        self.history.pop();
+        result
+    }
 
-        let len = moves.len();
-        let mut value = 0.;
-        let mut best_move: Option<Board> = None;
+    #[allow(clippy::too_many_arguments)]
+    fn search_moves(
+        &mut self,
+        board: Board,
+        depth: usize,
+        ply: usize,
+        alpha: i32,
+        beta: i32,
+        white: bool,
+        hash_move: Option<Move>,
+    ) -> (Option<Move>, i32) {
+        let previous = self.played.last().copied();
+        let mut picker = MovePicker::new(&board, hash_move, self.heuristics.killers(ply));
+        //Quiet moves searched so far, they lose history if a later move causes the cutoff
+        let mut tried = MoveList::new();
+        let mut value;
+        let mut best_move: Option<Move> = None;
         if white {
-            value = -CHECKMATE_VALUE - 3.0;
+            value = -INFINITE;
             let mut a = alpha;
-            if moves.len() == 0 {
-                return if board.white_kings & board.under_attack_by_black() != 0 {
-                    (board, -CHECKMATE_VALUE + 2.)
-                } else {
-                    (board, 0.0)
-                };
-            }
-
-            for i in OrderedMoves(moves) {
-                let eval = Board::alpha_beta(i, depth - 1, a, beta, false, age, trans_table);
+            while let Some(i) = picker.next(|m| self.heuristics.quiet_score(white, m, previous)) {
+                self.played.push(i);
+                let eval = self.alpha_beta(board.make_move(i), depth - 1, ply + 1, a, beta, false);
+                self.played.pop();
+                if self.aborted {
+                    return (None, 0);
+                }
                 if eval.1 > value {
                     value = eval.1;
                     best_move = Some(i);
+                    self.update_pv(ply, i);
                 }
 
-                a = f32::max(a, value);
+                a = a.max(value);
+                if is_quiet(i) {
+                    tried.push(i);
+                }
                 if value >= beta {
+                    if is_quiet(i) {
+                        self.heuristics
+                            .update(ply, depth, white, i, previous, &tried);
+                    }
                     break;
                 }
-                
             }
-        } else {
-            value = CHECKMATE_VALUE + 3.0;
-            let mut b = beta;
-            if moves.len() == 0 {
-                return if board.black_kings & board.under_attack_by_white() != 0 {
-                    (board, CHECKMATE_VALUE - 2.)
+            if best_move.is_none() {
+                return if board.white_kings & board.under_attack_by_black() != 0 {
+                    (None, -MATE + ply as i32)
                 } else {
-                    (board, 0.0)
+                    (None, 0)
                 };
             }
-            for i in OrderedMoves(moves) {
-                let eval = Board::alpha_beta(i, depth - 1, alpha, b, true, age, trans_table);
+        } else {
+            value = INFINITE;
+            let mut b = beta;
+            while let Some(i) = picker.next(|m| self.heuristics.quiet_score(white, m, previous)) {
+                self.played.push(i);
+                let eval = self.alpha_beta(board.make_move(i), depth - 1, ply + 1, alpha, b, true);
+                self.played.pop();
+                if self.aborted {
+                    return (None, 0);
+                }
                 if eval.1 < value {
                     value = eval.1;
-
                     best_move = Some(i);
+                    self.update_pv(ply, i);
                 }
 
-                b = f32::min(b, value);
+                b = b.min(value);
+                if is_quiet(i) {
+                    tried.push(i);
+                }
                 if value <= alpha {
+                    if is_quiet(i) {
+                        self.heuristics
+                            .update(ply, depth, white, i, previous, &tried);
+                    }
                     break;
                 }
-                
+            }
+            if best_move.is_none() {
+                return if board.black_kings & board.under_attack_by_white() != 0 {
+                    (None, MATE - ply as i32)
+                } else {
+                    (None, 0)
+                };
             }
         }
-        if value < -CHECKMATE_THRESHOLD {
-            value += 1.;
+        //The value is exact inside the window, otherwise it only bounds the true score from one side
+        let (lower_bound, upper_bound) = if value <= alpha {
+            (-INFINITE, value)
+        } else if value >= beta {
+            (value, INFINITE)
+        } else {
+            (value, value)
+        };
+        self.trans_table.store(TransEntry {
+            key: board.zobrist(),
+            depth,
+            lower_bound: score_to_table(lower_bound, ply),
+            upper_bound: score_to_table(upper_bound, ply),
+            response: best_move.unwrap(),
+            age: self.age,
+        });
+        (best_move, value)
+    }
+}
+
+impl<E: Evaluator> Searcher<'_, E> {
+    //Keeps searching captures and promotions past the horizon so the evaluation is never taken in the middle of an
+    //exchange. The side to move can always stand pat on the static evaluation instead of capturing.
+    fn quiescence(&mut self, board: Board, mut alpha: i32, mut beta: i32, white: bool) -> i32 {
+        self.nodes += 1;
+        if (self.nodes & (CHECK_INTERVAL - 1) == 0) && self.should_stop() {
+            return 0;
         }
-        if value > CHECKMATE_THRESHOLD {
-            value -= 1.;
+
+        let stand_pat = self.evaluator.evaluate(&board);
+        if white {
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+        } else {
+            if stand_pat <= alpha {
+                return stand_pat;
+            }
+            beta = beta.min(stand_pat);
         }
-        match trans_table.get_mut(&board) {
-            None => {
-                if value <= alpha {
-                    trans_table.insert(
-                        board,
-                        TransEntry {
-                            depth: depth,
-                            lower_bound: -INFINITY,
-                            upper_bound: value,
-                            response: best_move.unwrap(),
-                            age,
-                        },
-                    );
-                } else if (value > alpha) & (value < beta) {
-                    trans_table.insert(
-                        board,
-                        TransEntry {
-                            depth: depth,
-                            lower_bound: value,
-                            upper_bound: value,
-                            response: best_move.unwrap(),
-                            age,
-                        },
-                    );
-                } else if value >= beta {
-                    trans_table.insert(
-                        board,
-                        TransEntry {
-                            depth: depth,
-                            lower_bound: value,
-                            upper_bound: INFINITY,
-                            response: best_move.unwrap(),
-                            age,
-                        },
-                    );
-                }
+
+        let mut picker = MovePicker::noisy(&board);
+        let mut value = stand_pat;
+        while let Some(i) = picker.next(|_| 0) {
+            let eval = self.quiescence(board.make_move(i), alpha, beta, !white);
+            if self.aborted {
+                return 0;
             }
-            Some(result) => {
-                if result.depth <= depth {
-                    if value <= alpha {
-                        *result = TransEntry {
-                            depth: depth,
-                            lower_bound: -INFINITY,
-                            upper_bound: value,
-                            response: best_move.unwrap(),
-                            age,
-                        };
-                    } else if (value > alpha) & (value < beta) {
-                        *result = TransEntry {
-                            depth: depth,
-                            lower_bound: value,
-                            upper_bound: value,
-                            response: best_move.unwrap(),
-                            age,
-                        };
-                    } else if value >= beta {
-                        *result = TransEntry {
-                            depth: depth,
-                            lower_bound: value,
-                            upper_bound: INFINITY,
-                            response: best_move.unwrap(),
-                            age,
-                        };
-                    }
-                }
+            if white {
+                value = value.max(eval);
+                alpha = alpha.max(value);
+            } else {
+                value = value.min(eval);
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
             }
         }
-        return (best_move.unwrap(), value);
+        value
+    }
+}
+
+//Mate scores are stored relative to the position rather than the root, the same position can be reached at a
+//different distance from the root in a later search
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if (MATE_BOUND + 1..=MATE).contains(&score) {
+        score + ply as i32
+    } else if (-MATE..-MATE_BOUND).contains(&score) {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if (MATE_BOUND + 1..=MATE).contains(&score) {
+        score - ply as i32
+    } else if (-MATE..-MATE_BOUND).contains(&score) {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+//Moves to mate for a mate score, negative when the side the score is for is being mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score > MATE_BOUND {
+        Some((MATE - score + 1) / 2)
+    } else if score < -MATE_BOUND {
+        Some(-((MATE + score + 1) / 2))
+    } else {
+        None
     }
 }
 
-struct OrderedMoves(Vec<(Board, i32)>);
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::Ordering,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::super::{evaluation::MaterialEvaluator, history::History};
+    use super::{
+        mate_in, score_from_table, score_to_table, Board, Move, MovePicker, SearchInfo,
+        SearchLimits, Searcher, TranspositionTable, MATE, MAX_DEPTH,
+    };
+
+    fn search(fen: &str, depth: usize) -> (String, i32) {
+        let info = Board::from_fen(fen).unwrap().iterative_search(
+            &SearchLimits::depth(depth),
+            0,
+            &mut TranspositionTable::new(1),
+            &mut History::new(),
+            &mut MaterialEvaluator,
+            |_| (),
+        );
+        (info.best_move().unwrap().to_uci(), info.score)
+    }
+
+    #[test]
+    fn finds_mates() {
+        assert_eq!(
+            search(
+                "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+                3
+            ),
+            ("h5f7".to_string(), MATE - 1)
+        );
+        assert_eq!(mate_in(MATE - 1), Some(1));
+        //Scores are from white's side, black mating comes out negative
+        let (best_move, score) = search("r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1", 3);
+        assert_eq!(best_move, "a8a1");
+        assert_eq!(mate_in(score), Some(-1));
+        assert_eq!(mate_in(-score), Some(1));
+        //The king has to come over before the rook can mate
+        let (_, score) = search("k7/8/2K5/8/8/8/8/7R w - - 0 1", 5);
+        assert_eq!(mate_in(score), Some(2));
+        assert_eq!(mate_in(350), None);
+    }
 
-impl Iterator for OrderedMoves {
-    type Item = Board;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.0.len() == 0 {
-            return None;
+    //Score of a search where every position the side to play can move to has already been played
+    fn after_repeats(fen: &str, depth: usize) -> i32 {
+        let board = Board::from_fen(fen).unwrap();
+        let mut history = History::new();
+        for m in board.legal_moves() {
+            history.push(board.make_move(m).zobrist());
         }
-        let mut best_index = 0;
-        let mut best_score = 0;
-        for i in self.0.iter_mut().enumerate() {
-            if i.1 .1 > best_score {
-                best_score = i.1 .1;
-                best_index = i.0;
-            }
+        board
+            .iterative_search(
+                &SearchLimits::depth(depth),
+                0,
+                &mut TranspositionTable::new(1),
+                &mut history,
+                &mut MaterialEvaluator,
+                |_| (),
+            )
+            .score
+    }
+
+    #[test]
+    fn draws() {
+        //A rook up, but every move repeats a position
+        assert_eq!(after_repeats("k7/8/8/8/8/8/8/K6R w - - 20 40", 1), 0);
+        //The same positions came before the last capture or pawn move, so they can't be repeats
+        assert!(after_repeats("k7/8/8/8/8/8/8/K6R w - - 0 40", 1) > 400);
+        //Every move reaches the hundredth half move without mate
+        assert_eq!(search("k7/8/8/8/8/8/8/K6R w - - 99 90", 3).1, 0);
+        assert!(search("k7/8/8/8/8/8/8/K6R w - - 90 90", 3).1 > 400);
+    }
+
+    #[test]
+    fn quiescence_sees_recaptures() {
+        //Taking d5 wins a pawn on the board right after the capture, c6 takes the queen back next move
+        let fen = "4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1";
+        let board = Board::from_fen(fen).unwrap();
+        let grab = board.make_move(Move::from_uci("d1d5").unwrap());
+        for m in board.legal_moves() {
+            assert!(board.make_move(m).material() <= grab.material());
         }
-        let best = self.0.get_mut(best_index).unwrap();
-        if best.1 == -1 {
-            return None;
+        let (best_move, score) = search(fen, 1);
+        assert_ne!(best_move, "d1d5");
+        assert!(score > 600);
+    }
+
+    #[test]
+    fn mate_scores_in_the_table() {
+        //Mated three half moves after a node four half moves from the root
+        let score = -MATE + 7;
+        assert_eq!(score_to_table(score, 4), -MATE + 3);
+        assert_eq!(score_from_table(score_to_table(score, 4), 2), -MATE + 5);
+        assert_eq!(score_from_table(score_to_table(MATE - 7, 4), 4), MATE - 7);
+        assert_eq!(score_to_table(120, 9), 120);
+    }
+
+    #[test]
+    fn principal_variation() {
+        let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+        let mut depths = Vec::new();
+        let info = board.iterative_search(
+            &SearchLimits::depth(5),
+            0,
+            &mut TranspositionTable::new(1),
+            &mut History::new(),
+            &mut MaterialEvaluator,
+            |info| depths.push(info.depth),
+        );
+        assert_eq!(depths, [1, 2, 3, 4, 5]);
+        //The line ends in mate and every move of it is legal where it is played
+        assert_eq!(info.pv.len(), 3);
+        let mut position = board;
+        for m in &info.pv {
+            assert!(position.legal_moves().contains(m), "{}", m.to_uci());
+            position = position.make_move(*m);
+        }
+        assert!(position.legal_moves().is_empty());
+    }
+
+    //Whether the deadline is the given time after the limits were made, which happened between before and after
+    fn deadline_in(
+        deadline: Option<Instant>,
+        before: Instant,
+        after: Instant,
+        millis: u64,
+    ) -> bool {
+        let expected = Duration::from_millis(millis);
+        deadline.is_some_and(|deadline| {
+            (deadline >= before + expected) & (deadline <= after + expected)
+        })
+    }
+
+    #[test]
+    fn deadlines() {
+        let before = Instant::now();
+        let limits = SearchLimits::move_time(Duration::from_millis(1000));
+        let after = Instant::now();
+        //The move overhead is kept back
+        assert!(deadline_in(limits.soft_deadline, before, after, 970));
+        assert_eq!(limits.soft_deadline, limits.hard_deadline);
+
+        //A tenth of the clock for each of the ten moves to go, up to four times that on a hard position
+        let before = Instant::now();
+        let limits = SearchLimits::clock(Duration::from_secs(10), Duration::ZERO, Some(10));
+        let after = Instant::now();
+        assert!(deadline_in(limits.soft_deadline, before, after, 997));
+        assert!(deadline_in(limits.hard_deadline, before, after, 3988));
+
+        //Most of the increment is spent, but never more than half the clock on one move
+        let before = Instant::now();
+        let limits = SearchLimits::clock(Duration::from_secs(10), Duration::from_secs(2), Some(10));
+        let after = Instant::now();
+        assert!(deadline_in(limits.soft_deadline, before, after, 2497));
+        assert!(deadline_in(limits.hard_deadline, before, after, 4985));
+    }
+
+    //Starts a search on its own thread the way the UCI front-end does
+    fn spawn_search(fen: &str, limits: SearchLimits) -> thread::JoinHandle<SearchInfo> {
+        let board = Board::from_fen(fen).unwrap();
+        thread::spawn(move || {
+            board.iterative_search(
+                &limits,
+                0,
+                &mut TranspositionTable::new(1),
+                &mut History::new(),
+                &mut MaterialEvaluator,
+                |_| (),
+            )
+        })
+    }
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn stop_and_hard_deadline() {
+        let limits = SearchLimits::depth(MAX_DEPTH);
+        let stop = limits.stop.clone();
+        let search = spawn_search(START, limits);
+        thread::sleep(Duration::from_millis(200));
+        stop.store(true, Ordering::Relaxed);
+        let stopped = Instant::now();
+        let info = search.join().unwrap();
+        assert!(stopped.elapsed() < Duration::from_secs(1));
+        assert!((info.depth < MAX_DEPTH) & info.best_move().is_some());
+
+        let start = Instant::now();
+        let limits = SearchLimits {
+            hard_deadline: Some(start + Duration::from_millis(200)),
+            ..SearchLimits::depth(MAX_DEPTH)
+        };
+        let info = spawn_search(START, limits).join().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(1200));
+        assert!((info.depth < MAX_DEPTH) & info.best_move().is_some());
+    }
+
+    #[test]
+    fn infinite_waits_for_stop() {
+        //Only one legal move, which ends any other search after the first iteration
+        let limits = SearchLimits::infinite();
+        let stop = limits.stop.clone();
+        let search = spawn_search("k7/8/8/8/8/8/1r6/K7 w - - 0 1", limits);
+        thread::sleep(Duration::from_millis(200));
+        assert!(!search.is_finished());
+        stop.store(true, Ordering::Relaxed);
+        assert_eq!(search.join().unwrap().best_move().unwrap().to_uci(), "a1b2");
+    }
+
+    #[test]
+    fn hash_move_first() {
+        //Rh8 mates, taking the knight is the first move the picker would try without the hash move
+        let board = Board::from_fen("k7/8/1K6/8/8/8/8/3n3R w - - 0 1").unwrap();
+        assert_eq!(
+            MovePicker::new(&board, None, [None; 2]).next(|_| 0),
+            Move::from_uci("h1d1")
+        );
+
+        let limits = SearchLimits::depth(MAX_DEPTH);
+        let mut trans_table = TranspositionTable::new(1);
+        let mut history = History::new();
+        let mut evaluator = MaterialEvaluator;
+        let mut searcher =
+            Searcher::new(&mut trans_table, &mut evaluator, &mut history, 0, &limits);
+        //The first move the root's picker hands out, set up the way search_root sets it up
+        let first = |searcher: &Searcher<MaterialEvaluator>| {
+            MovePicker::new(
+                &board,
+                searcher.root_hash_move(&board),
+                searcher.heuristics.killers(0),
+            )
+            .next(|_| 0)
+        };
+        for depth in 1..=2 {
+            searcher.search_root(board, depth);
+            assert_eq!(first(&searcher), searcher.pv[0].first().copied());
         }
-        best.1 = -1;
-        Some(best.0)
+        assert_eq!(first(&searcher), Move::from_uci("h1h8"));
+        //Without a line from the last iteration the table's move comes first
+        searcher.pv[0].clear();
+        assert_eq!(first(&searcher), Move::from_uci("h1h8"));
     }
 }
@@ -1,4 +1,5 @@
-use crate::bot::pseudomoves::{bishop_moves, rook_moves, KING_MOVES};
+use crate::bot::magic_bitboards::{bishop_moves, rook_moves};
+use crate::bot::pseudomoves::KING_MOVES;
 
 use super::{
     pseudomoves::{KNIGHT_MOVES, PAWN_ATTACKS, PAWN_MOVES},
@@ -16,8 +17,7 @@ impl Board {
 
             if mask & self.white_pawns != 0 {
                 return self.single_pawn_moves(square, true, checkmask, pinmask_d, pinmask_hv);
-            }
-            else if mask & self.white_knights != 0 {
+            } else if mask & self.white_knights != 0 {
                 return self.single_knight_moves(square, true, checkmask, pinmask_d, pinmask_hv);
             } else if mask & self.white_queens != 0 {
                 return self.single_queen_moves(square, true, checkmask, pinmask_d, pinmask_hv);
@@ -68,7 +68,10 @@ impl Board {
                         ),
                 );
                 res.extend(BitBoardIter(
-                    PAWN_ATTACKS[0][square] & pinmask_d & checkmask & (self.occupied_by_black | self.en_passant),
+                    PAWN_ATTACKS[0][square]
+                        & pinmask_d
+                        & checkmask
+                        & (self.occupied_by_black | self.en_passant),
                 ));
             } else {
                 res.extend(
@@ -77,7 +80,9 @@ impl Board {
                     }),
                 );
                 res.extend(BitBoardIter(
-                    PAWN_ATTACKS[0][square] & checkmask & (self.occupied_by_black | self.en_passant),
+                    PAWN_ATTACKS[0][square]
+                        & checkmask
+                        & (self.occupied_by_black | self.en_passant),
                 ));
             }
         } else {
@@ -89,7 +94,10 @@ impl Board {
                         ),
                 );
                 res.extend(BitBoardIter(
-                    PAWN_ATTACKS[1][square] & pinmask_d & checkmask & (self.occupied_by_white | self.en_passant),
+                    PAWN_ATTACKS[1][square]
+                        & pinmask_d
+                        & checkmask
+                        & (self.occupied_by_white | self.en_passant),
                 ));
             } else {
                 res.extend(
@@ -98,7 +106,9 @@ impl Board {
                     }),
                 );
                 res.extend(BitBoardIter(
-                    PAWN_ATTACKS[1][square] & checkmask & (self.occupied_by_white | self.en_passant),
+                    PAWN_ATTACKS[1][square]
+                        & checkmask
+                        & (self.occupied_by_white | self.en_passant),
                 ));
             }
         }
@@ -133,16 +143,27 @@ impl Board {
         pinmask_hv: BitBoard,
     ) -> Vec<usize> {
         let mask = ((1 as BitBoard) << square);
-        let moveable = if white {self.black_or_empty()} else {self.white_or_empty()};
+        let moveable = if white {
+            self.black_or_empty()
+        } else {
+            self.white_or_empty()
+        };
         if mask & (pinmask_d | pinmask_hv) != 0 {
-            let mut moves: Vec<usize> = BitBoardIter(bishop_moves(square, !self.occupied) & checkmask & moveable & pinmask_d).collect();
-            moves.extend(BitBoardIter(rook_moves(square, !self.occupied) & checkmask & moveable & pinmask_hv));
-            return moves;
-        }
-        else {
-            let mut moves: Vec<usize> = BitBoardIter(bishop_moves(square, !self.occupied) & checkmask & moveable).collect();
-            moves.extend(BitBoardIter(rook_moves(square, !self.occupied) & checkmask & moveable));
-            return moves;
+            let mut moves: Vec<usize> = BitBoardIter(
+                bishop_moves(square, !self.occupied) & checkmask & moveable & pinmask_d,
+            )
+            .collect();
+            moves.extend(BitBoardIter(
+                rook_moves(square, !self.occupied) & checkmask & moveable & pinmask_hv,
+            ));
+            moves
+        } else {
+            let mut moves: Vec<usize> =
+                BitBoardIter(bishop_moves(square, !self.occupied) & checkmask & moveable).collect();
+            moves.extend(BitBoardIter(
+                rook_moves(square, !self.occupied) & checkmask & moveable,
+            ));
+            moves
         }
     }
 
@@ -155,11 +176,15 @@ impl Board {
         pinmask_hv: BitBoard,
     ) -> Vec<usize> {
         let mask = ((1 as BitBoard) << square);
-        let moveable = if white {self.black_or_empty()} else {self.white_or_empty()};
+        let moveable = if white {
+            self.black_or_empty()
+        } else {
+            self.white_or_empty()
+        };
         if mask & (pinmask_d | pinmask_hv) != 0 {
-            BitBoardIter(bishop_moves(square, !self.occupied) & checkmask & moveable & pinmask_d).collect()
-        }
-        else {
+            BitBoardIter(bishop_moves(square, !self.occupied) & checkmask & moveable & pinmask_d)
+                .collect()
+        } else {
             BitBoardIter(bishop_moves(square, !self.occupied) & checkmask & moveable).collect()
         }
     }
@@ -173,31 +198,51 @@ impl Board {
         pinmask_hv: BitBoard,
     ) -> Vec<usize> {
         let mask = ((1 as BitBoard) << square);
-        let moveable = if white {self.black_or_empty()} else {self.white_or_empty()};
+        let moveable = if white {
+            self.black_or_empty()
+        } else {
+            self.white_or_empty()
+        };
         if mask & (pinmask_d | pinmask_hv) != 0 {
-            BitBoardIter(rook_moves(square, !self.occupied) & checkmask & moveable & pinmask_hv).collect()
-        }
-        else {
+            BitBoardIter(rook_moves(square, !self.occupied) & checkmask & moveable & pinmask_hv)
+                .collect()
+        } else {
             BitBoardIter(rook_moves(square, !self.occupied) & checkmask & moveable).collect()
         }
     }
 
-    fn single_king_moves(
-        &self,
-        square: usize,
-        white: bool,
-        checkmask: BitBoard,
-    ) -> Vec<usize> {
+    fn single_king_moves(&self, square: usize, white: bool, checkmask: BitBoard) -> Vec<usize> {
         let mask = ((1 as BitBoard) << square);
-        let moveable = if white {self.black_or_empty()} else {self.white_or_empty()};
-        let under_attack = if white {self.under_attack_by_black()} else {self.under_attack_by_white()};
-        let mut moves: Vec<usize> = BitBoardIter(KING_MOVES[square] & !under_attack & moveable).collect();
+        let moveable = if white {
+            self.black_or_empty()
+        } else {
+            self.white_or_empty()
+        };
+        let under_attack = if white {
+            self.under_attack_by_black()
+        } else {
+            self.under_attack_by_white()
+        };
+        let mut moves: Vec<usize> =
+            BitBoardIter(KING_MOVES[square] & !under_attack & moveable).collect();
         if white {
-            if (self.castle & 0x90 == 0x90) && under_attack & 0x70 == 0 { moves.push(7); }
-            if (self.castle & 0x11 == 0x11) && under_attack & 0x1c == 0 { moves.push(0); }
+            if (self.castle & 0x90 == 0x90) && under_attack & 0x70 == 0 {
+                moves.push(7);
+            }
+            if (self.castle & 0x11 == 0x11) && under_attack & 0x1c == 0 {
+                moves.push(0);
+            }
         } else {
-            if (self.castle & 0x9000000000000000 == 0x9000000000000000) && under_attack & 0x7000000000000000 == 0 {moves.push(63)};
-            if (self.castle & 0x1100000000000000 == 0x1100000000000000) && under_attack & 0x1c00000000000000 == 0 {moves.push(56)};
+            if (self.castle & 0x9000000000000000 == 0x9000000000000000)
+                && under_attack & 0x7000000000000000 == 0
+            {
+                moves.push(63)
+            };
+            if (self.castle & 0x1100000000000000 == 0x1100000000000000)
+                && under_attack & 0x1c00000000000000 == 0
+            {
+                moves.push(56)
+            };
         }
         moves
     }
@@ -0,0 +1,120 @@
+use std::mem;
+
+use super::board_move::Move;
+
+pub const DEFAULT_HASH_MB: usize = 16;
+
+#[derive(Clone, Copy)]
+pub struct TransEntry {
+    pub key: u64,
+    pub depth: usize,
+    //Mate scores count the half moves from this position rather than from the root
+    pub lower_bound: i32,
+    pub upper_bound: i32,
+    pub response: Move,
+    pub age: usize,
+}
+
+//Fixed size table indexed by the low bits of the Zobrist key, the full key is kept in the entry to tell positions
+//sharing a slot apart
+pub struct TranspositionTable {
+    entries: Vec<Option<TransEntry>>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let len = Self::entries_for(size_mb);
+        TranspositionTable {
+            entries: vec![None; len],
+            mask: len - 1,
+        }
+    }
+
+    //Largest power of two number of entries that fits in the size given
+    fn entries_for(size_mb: usize) -> usize {
+        let bytes = size_mb.max(1) * 1024 * 1024;
+        let len = bytes / mem::size_of::<Option<TransEntry>>();
+        if len.is_power_of_two() {
+            len
+        } else {
+            len.next_power_of_two() / 2
+        }
+    }
+
+    pub fn resize(&mut self, size_mb: usize) {
+        *self = TranspositionTable::new(size_mb);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+
+    #[inline]
+    pub fn get(&self, key: u64) -> Option<&TransEntry> {
+        self.entries[key as usize & self.mask]
+            .as_ref()
+            .filter(|entry| entry.key == key)
+    }
+
+    //An entry left from an earlier search is always replaced, its bounds may be stale. Within one search an entry is
+    //only replaced by one searched at least as deep.
+    #[inline]
+    pub fn store(&mut self, entry: TransEntry) {
+        let slot = &mut self.entries[entry.key as usize & self.mask];
+        let replace = match slot {
+            None => true,
+            Some(old) => (old.age != entry.age) | (entry.depth >= old.depth),
+        };
+        if replace {
+            *slot = Some(entry);
+        }
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_HASH_MB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Move, TransEntry, TranspositionTable};
+
+    fn entry(key: u64, depth: usize, age: usize) -> TransEntry {
+        TransEntry {
+            key,
+            depth,
+            lower_bound: depth as i32,
+            upper_bound: depth as i32,
+            response: Move::from_uci("e2e4").unwrap(),
+            age,
+        }
+    }
+
+    fn depth(table: &TranspositionTable, key: u64) -> Option<usize> {
+        table.get(key).map(|entry| entry.depth)
+    }
+
+    #[test]
+    fn replacement() {
+        let mut table = TranspositionTable::new(1);
+        table.store(entry(7, 6, 0));
+        //A shallower search of the same position in the same search doesn't replace it
+        table.store(entry(7, 3, 0));
+        assert_eq!(depth(&table, 7), Some(6));
+        //A newer search does, however shallow
+        table.store(entry(7, 2, 1));
+        assert_eq!(depth(&table, 7), Some(2));
+
+        //Another position sharing the slot
+        let other = 7 + (table.mask as u64 + 1);
+        table.store(entry(other, 1, 1));
+        assert_eq!(depth(&table, 7), Some(2));
+        assert_eq!(depth(&table, other), None);
+        table.store(entry(other, 1, 2));
+        assert_eq!(depth(&table, 7), None);
+        assert_eq!(depth(&table, other), Some(1));
+    }
+}
@@ -67,7 +67,7 @@ impl Iterator for BitBoardIter {
     }
 }
 
-fn string_square(square: usize) -> String {
+pub fn string_square(square: usize) -> String {
     let file = square % 8;
     let rank = square / 8;
     let mut res = String::new();
@@ -95,3 +95,38 @@ fn string_square(square: usize) -> String {
     }
     res
 }
+
+pub fn parse_square(square: &str) -> Option<usize> {
+    let mut chars = square.chars();
+    let file = match chars.next()? {
+        c @ 'a'..='h' => c as usize - 'a' as usize,
+        _ => return None,
+    };
+    let rank = match chars.next()? {
+        c @ '1'..='8' => c as usize - '1' as usize,
+        _ => return None,
+    };
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(rank * 8 + file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_square, string_square};
+
+    #[test]
+    fn squares() {
+        assert_eq!(parse_square("a1"), Some(0));
+        assert_eq!(parse_square("h1"), Some(7));
+        assert_eq!(parse_square("e4"), Some(28));
+        assert_eq!(parse_square("h8"), Some(63));
+        for square in 0..64 {
+            assert_eq!(parse_square(&string_square(square)), Some(square));
+        }
+        for text in ["", "a", "a0", "a9", "i1", "A1", "a10", "1a"] {
+            assert_eq!(parse_square(text), None, "{}", text);
+        }
+    }
+}
@@ -1,12 +1,98 @@
-use super::{
-    utils::{print_bit_board, BitBoardIter},
-    Board,
-};
+use super::{board_move::Move, utils::BitBoardIter, BitBoard, Board};
 
 impl Board {
+    //Polyglot key of the position, kept up to date by make_move
+    #[inline]
     pub fn zobrist(&self) -> u64 {
+        self.zobrist_key
+    }
+
+    //Works the key out from scratch, only needed when a board is set up rather than reached by a move
+    pub fn compute_zobrist(&self) -> u64 {
         let mut piece: u64 = 0;
-        let piece_order = vec![
+        for (kind, pieces) in self.polyglot_pieces().into_iter().enumerate() {
+            for square in BitBoardIter(pieces) {
+                piece ^= RANDOM_PIECE[64 * kind + square];
+            }
+        }
+        piece ^ self.castle_key() ^ self.en_passant_key() ^ self.turn_key()
+    }
+
+    //Key made of the pawn squares only, positions with the same pawns share their pawn structure score
+    #[inline]
+    pub fn pawn_key(&self) -> u64 {
+        self.pawn_key
+    }
+
+    pub fn compute_pawn_key(&self) -> u64 {
+        let mut key = 0;
+        for (kind, pieces) in [self.black_pawns, self.white_pawns].into_iter().enumerate() {
+            for square in BitBoardIter(pieces) {
+                key ^= RANDOM_PIECE[64 * kind + square];
+            }
+        }
+        key
+    }
+
+    //Changes the key of a board reached from previous by m. Only the squares of the piece moved, the piece captured
+    //and the rook of a castling move are touched, then the rights, en passant file and turn that changed.
+    pub(super) fn update_zobrist(&mut self, previous: &Board, m: Move) {
+        let from = m.from_square as usize;
+        let to = m.to_square as usize;
+        let mut key = previous.zobrist_key;
+        let mut pawn_key = previous.pawn_key;
+        let mut toggle = |kind: usize, square: usize| {
+            key ^= RANDOM_PIECE[64 * kind + square];
+            //The first two kinds are the pawns
+            if kind < 2 {
+                pawn_key ^= RANDOM_PIECE[64 * kind + square];
+            }
+        };
+
+        let moved = previous.piece_kind(from).unwrap();
+        toggle(moved, from);
+        //A promoted pawn arrives as the piece it became
+        toggle(self.piece_kind(to).unwrap(), to);
+        if let Some(captured) = previous.piece_kind(to) {
+            toggle(captured, to);
+        } else if (moved < 2) & (from % 8 != to % 8) {
+            //En passant, the pawn taken is beside the one taking it. The pawn kinds alternate colours.
+            toggle(moved ^ 1, from - from % 8 + to % 8);
+        }
+        //Castling is a two square king move, the rook jumps over the king
+        if (moved >= 10) & (from.abs_diff(to) == 2) {
+            let (rook_from, rook_to) = if to > from {
+                (from + 3, from + 1)
+            } else {
+                (from - 4, from - 1)
+            };
+            toggle(moved - 4, rook_from);
+            toggle(moved - 4, rook_to);
+        }
+
+        key ^= previous.castle_key() ^ self.castle_key();
+        key ^= previous.en_passant_key() ^ self.en_passant_key();
+        key ^= previous.turn_key() ^ self.turn_key();
+        self.zobrist_key = key;
+        self.pawn_key = pawn_key;
+    }
+
+    //Polyglot piece kind on the square
+    #[inline]
+    fn piece_kind(&self, square: usize) -> Option<usize> {
+        let mask: BitBoard = 1 << square;
+        if self.occupied & mask == 0 {
+            return None;
+        }
+        self.polyglot_pieces()
+            .iter()
+            .position(|pieces| pieces & mask != 0)
+    }
+
+    //Bitboards in the order of Polyglot's piece kinds
+    #[inline]
+    fn polyglot_pieces(&self) -> [BitBoard; 12] {
+        [
             self.black_pawns,
             self.white_pawns,
             self.black_knights,
@@ -19,14 +105,11 @@ impl Board {
             self.white_queens,
             self.black_kings,
             self.white_kings,
-        ];
-        for pieces in piece_order.into_iter().enumerate() {
-            for square in BitBoardIter(pieces.1) {
-                let offset = 64 * pieces.0 + square;
-                piece ^= RANDOM_PIECE[offset];
-            }
-        }
+        ]
+    }
 
+    #[inline]
+    fn castle_key(&self) -> u64 {
         let mut castle = 0;
         if self.castle & 0x80 != 0 {
             castle ^= RANDOM_CASTLE[0];
@@ -40,16 +123,28 @@ impl Board {
         if self.castle & 0x100000000000000 != 0 {
             castle ^= RANDOM_CASTLE[3];
         }
+        castle
+    }
 
+    //Polyglot only counts the en passant square when a pawn can actually take on it
+    #[inline]
+    fn en_passant_key(&self) -> u64 {
         let en_passant_bitboard = self.get_en_passant();
-        let mut en_passant = 0;
         if en_passant_bitboard != 0 {
             let file = en_passant_bitboard.trailing_zeros() % 8;
-            en_passant = RANDOM_EN_PASSANT[file as usize];
+            RANDOM_EN_PASSANT[file as usize]
+        } else {
+            0
         }
+    }
 
-        let turn = if self.white_to_play { RANDOM_TURN } else { 0 };
-        piece ^ castle ^ turn ^ en_passant
+    #[inline]
+    fn turn_key(&self) -> u64 {
+        if self.white_to_play {
+            RANDOM_TURN
+        } else {
+            0
+        }
     }
 }
 
@@ -841,3 +936,80 @@ const RANDOM_EN_PASSANT: [u64; 8] = [
     0x67A34DAC4356550B,
 ];
 const RANDOM_TURN: u64 = 0xF8D626AAAF278509;
+
+#[cfg(test)]
+mod tests {
+    use super::super::{board_move::Move, Board};
+
+    fn play(moves: &[&str]) -> Board {
+        moves.iter().fold(Board::default(), |board, text| {
+            board.make_move(Move::from_uci(text).unwrap())
+        })
+    }
+
+    //Reference keys from the Polyglot book format specification
+    #[test]
+    fn polyglot_reference_keys() {
+        assert_eq!(play(&[]).zobrist(), 0x463b96181691fc9c);
+        assert_eq!(play(&["e2e4"]).zobrist(), 0x823c9b50fd114196);
+        assert_eq!(play(&["e2e4", "d7d5"]).zobrist(), 0x0756b94461c50fb0);
+        assert_eq!(
+            play(&["e2e4", "d7d5", "e4e5"]).zobrist(),
+            0x662fafb965db29d4
+        );
+        assert_eq!(
+            play(&["e2e4", "d7d5", "e4e5", "f7f5"]).zobrist(),
+            0x22a48b5a8e47ff78
+        );
+        assert_eq!(
+            play(&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"]).zobrist(),
+            0x652a607ca3f242c1
+        );
+        assert_eq!(
+            play(&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"]).zobrist(),
+            0x00fdd303c946bdd9
+        );
+        assert_eq!(
+            play(&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"]).zobrist(),
+            0x3c8123ea7b067637
+        );
+        assert_eq!(
+            play(&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"]).zobrist(),
+            0x5c3f9b829b279560
+        );
+    }
+
+    fn check_tree(board: Board, depth: usize) {
+        assert_eq!(
+            board.zobrist(),
+            board.compute_zobrist(),
+            "{}",
+            board.to_fen()
+        );
+        assert_eq!(
+            board.pawn_key(),
+            board.compute_pawn_key(),
+            "{}",
+            board.to_fen()
+        );
+        if depth == 0 {
+            return;
+        }
+        for m in board.legal_moves() {
+            check_tree(board.make_move(m), depth - 1);
+        }
+    }
+
+    #[test]
+    fn incremental_matches_scratch() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/1P4p1/8/8/5pP1/8/6p1/R3K2R b KQkq g3 0 1",
+        ] {
+            check_tree(Board::from_fen(fen).unwrap(), 3);
+        }
+    }
+}
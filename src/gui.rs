@@ -1,18 +1,20 @@
 use rand::Rng;
-use std::collections::HashMap;
 use std::time::Duration;
 
 use sdl2::event::Event;
 use sdl2::image::{self, InitFlag, LoadTexture};
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Scancode};
 use sdl2::mouse::{self, MouseButton};
 use sdl2::pixels::Color;
 use sdl2::rect::{Point, Rect};
 use sdl2::render::{BlendMode, TextureAccess};
 
-use crate::bot::TransEntry;
 use crate::bot::board_move::{self, Move, PromotionPiece};
+use crate::bot::evaluation::StandardEvaluator;
+use crate::bot::history::History;
 use crate::bot::opening::OpeningBook;
+use crate::bot::search::SearchLimits;
+use crate::bot::transposition::TranspositionTable;
 use crate::{Board, Piece};
 
 const SQUARE_SIZE: u32 = 78;
@@ -22,7 +24,9 @@ const BORDER_WIDTH: u32 = 32;
 const SPRITE_SIZE: u32 = 426;
 const WINDOW_SIZE: u32 = (SQUARE_SIZE * 8) + (PADDING * 2) + (BORDER_WIDTH * 2);
 
-pub fn start_gui(board: Board) -> Result<(), String> {
+//Books are given the same way as the UCI Book option, a ';' separated list of bundled names or paths
+pub fn start_gui(board: Board, books: &str) -> Result<(), String> {
+    let books = OpeningBook::load_list(books)?;
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
@@ -37,17 +41,19 @@ pub fn start_gui(board: Board) -> Result<(), String> {
     let texture_creator = canvas.texture_creator();
     let mut texture = texture_creator
         .load_texture_bytes(include_bytes!("assets/pieces.png"))
-        .map_err(|err| format!("failed to load spritesheet surface: {}", err.to_string()))?;
+        .map_err(|err| format!("failed to load spritesheet surface: {}", err))?;
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
     let mut gui_state = GuiState {
         mouse_held_down: false,
         selected_square: (-1, -1),
-        board: board,
+        board,
+        history: History::new(),
         valid_moves: Vec::new(),
-        book: OpeningBook::new(include_bytes!("books/Elo2400.bin").to_vec()),
-        trans_table: HashMap::new(),
+        books,
+        trans_table: TranspositionTable::default(),
+        evaluator: StandardEvaluator::new(),
         age: 0,
     };
 
@@ -79,9 +85,13 @@ pub fn start_gui(board: Board) -> Result<(), String> {
         gui_state.valid_moves = if gui_state.selected_square == (-1, -1) {
             Vec::new()
         } else {
-            gui_state.board.valid_moves(
-                ((7 - gui_state.selected_square.1) * 8 + gui_state.selected_square.0) as usize,
-            )
+            let from = (7 - gui_state.selected_square.1) * 8 + gui_state.selected_square.0;
+            gui_state
+                .board
+                .legal_moves()
+                .into_iter()
+                .filter(|m| m.from_square as i32 == from)
+                .collect()
         };
         for file in 0..8 {
             for rank in 0..8 {
@@ -91,7 +101,8 @@ pub fn start_gui(board: Board) -> Result<(), String> {
                     canvas.set_draw_color(Color::RGB(65, 0, 0));
                 } else if gui_state
                     .valid_moves
-                    .contains(&(((7 - rank) * 8 + file) as usize))
+                    .iter()
+                    .any(|m| m.to_square as u32 == (7 - rank) * 8 + file)
                 {
                     canvas.set_draw_color(Color::RGB(65, 65, 65));
                 } else if (file + rank) % 2 == 0 {
@@ -141,22 +152,40 @@ fn check_for_click(e: &sdl2::EventPump, gui_state: &mut GuiState) {
             let y_square =
                 (e.mouse_state().y() - PADDING as i32 - BORDER_WIDTH as i32) / (SQUARE_SIZE as i32);
             if (x_square < 8) & (y_square < 8) {
-                if gui_state
-                    .valid_moves
-                    .contains(&(((7 - y_square) * 8 + x_square) as usize))
-                {
-                    let new_move = Move {
-                        to_square: ((7 - y_square) * 8 + x_square) as u16,
-                        from_square: ((7 - gui_state.selected_square.1) * 8
-                            + gui_state.selected_square.0)
-                            as u16,
-                        promotion_piece: PromotionPiece::None,
-                        weight: 0,
-                    };
+                let to = (7 - y_square) * 8 + x_square;
+                //Promotions are to a queen unless N, B or R is held down while clicking
+                let keyboard = e.keyboard_state();
+                let promotion_piece = if keyboard.is_scancode_pressed(Scancode::N) {
+                    PromotionPiece::Knight
+                } else if keyboard.is_scancode_pressed(Scancode::B) {
+                    PromotionPiece::Bishop
+                } else if keyboard.is_scancode_pressed(Scancode::R) {
+                    PromotionPiece::Rook
+                } else {
+                    PromotionPiece::Queen
+                };
+                let chosen = gui_state.valid_moves.iter().copied().find(|m| {
+                    (m.to_square as i32 == to)
+                        & ((m.promotion_piece == PromotionPiece::None)
+                            | (m.promotion_piece == promotion_piece))
+                });
+                if let Some(new_move) = chosen {
+                    gui_state.history.push(gui_state.board.zobrist());
                     gui_state.board = gui_state.board.make_move(new_move);
-                    gui_state.board = gui_state.board.find_move(6, gui_state.age, &mut gui_state.trans_table, &gui_state.book).0;
+                    let found = gui_state.board.find_move(
+                        &SearchLimits::depth(6),
+                        gui_state.age,
+                        &mut gui_state.trans_table,
+                        &gui_state.books,
+                        &mut gui_state.history,
+                        &mut gui_state.evaluator,
+                        |_| (),
+                    );
+                    if let Some(reply) = found.best_move {
+                        gui_state.history.push(gui_state.board.zobrist());
+                        gui_state.board = gui_state.board.make_move(reply);
+                    }
                     gui_state.age += 1;
-                    println!("{} {}", gui_state.age, gui_state.trans_table.len());
                     gui_state.selected_square = (-1, -1);
                 } else {
                     gui_state.selected_square = (x_square, y_square);
@@ -200,8 +229,10 @@ struct GuiState {
     mouse_held_down: bool,
     selected_square: (i32, i32),
     board: Board,
-    valid_moves: Vec<usize>,
-    book: OpeningBook,
-    trans_table: HashMap<Board, TransEntry>,
-    age: usize
+    history: History,
+    valid_moves: Vec<Move>,
+    books: Vec<OpeningBook>,
+    trans_table: TranspositionTable,
+    evaluator: StandardEvaluator,
+    age: usize,
 }
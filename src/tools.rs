@@ -0,0 +1,75 @@
+use std::fs;
+
+use crate::bot::book_builder::{merge_books, BookBuilder, MergeWeights};
+use crate::bot::opening::OpeningBook;
+use crate::bot::pgn::parse_pgn;
+
+//makebook <games.pgn> <book.bin> [max ply] [win draw loss weights]
+pub fn make_book(args: &[String]) -> Result<(), String> {
+    let (pgn_path, book_path) = match args {
+        [pgn_path, book_path, ..] => (pgn_path, book_path),
+        _ => {
+            return Err(
+                "usage: makebook <games.pgn> <book.bin> [max ply] [win draw loss]".to_string(),
+            )
+        }
+    };
+    let numbers = args[2..]
+        .iter()
+        .map(|x| x.parse::<u64>())
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|err| format!("invalid number: {}", err))?;
+
+    let mut builder = BookBuilder::new();
+    match numbers[..] {
+        [] => (),
+        [max_ply] => builder.max_ply = max_ply as usize,
+        [max_ply, win, draw, loss] => {
+            builder.max_ply = max_ply as usize;
+            builder.win_weight = win;
+            builder.draw_weight = draw;
+            builder.loss_weight = loss;
+        }
+        _ => {
+            return Err("expected a max ply and optionally win, draw and loss weights".to_string())
+        }
+    }
+
+    let text = fs::read_to_string(pgn_path)
+        .map_err(|err| format!("could not read {}: {}", pgn_path, err))?;
+    let games = parse_pgn(&text);
+    let skipped = games.iter().filter(|game| !builder.add_game(game)).count();
+    builder
+        .write(book_path)
+        .map_err(|err| format!("could not write {}: {}", book_path, err))?;
+    println!(
+        "{} games read, {} unfinished or only partly replayed",
+        games.len(),
+        skipped
+    );
+    Ok(())
+}
+
+//mergebook <book.bin> <sum|max|first> <books>...
+pub fn merge_book(args: &[String]) -> Result<(), String> {
+    let (out_path, merge, inputs) = match args {
+        [out_path, merge, inputs @ ..] if !inputs.is_empty() => (out_path, merge, inputs),
+        _ => return Err("usage: mergebook <book.bin> <sum|max|first> <books>...".to_string()),
+    };
+    let merge = MergeWeights::from_name(merge)
+        .ok_or_else(|| format!("unknown weight merge {}, expected sum, max or first", merge))?;
+    let books = inputs
+        .iter()
+        .map(|name| {
+            OpeningBook::load(name).map_err(|err| format!("could not load {}: {}", name, err))
+        })
+        .collect::<Result<Vec<OpeningBook>, String>>()?;
+    let bytes = merge_books(&books, merge);
+    fs::write(out_path, &bytes).map_err(|err| format!("could not write {}: {}", out_path, err))?;
+    println!(
+        "{} entries from {} books written",
+        bytes.len() / 16,
+        books.len()
+    );
+    Ok(())
+}
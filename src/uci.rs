@@ -0,0 +1,436 @@
+use std::io::{stdin, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::bot::board_move::Move;
+use crate::bot::evaluation::{Evaluator, EvaluatorKind};
+use crate::bot::history::History;
+use crate::bot::opening::{BookPolicy, OpeningBook, DEFAULT_BOOK};
+use crate::bot::search::{mate_in, SearchInfo, SearchLimits};
+use crate::bot::transposition::{TranspositionTable, DEFAULT_HASH_MB};
+use crate::Board;
+
+const DEFAULT_DEPTH: usize = 8;
+
+struct UciState {
+    board: Board,
+    history: History,
+    trans_table: Arc<Mutex<TranspositionTable>>,
+    //Consulted in order, the first book with a legal move for the position is used
+    books: Arc<Vec<OpeningBook>>,
+    own_book: bool,
+    book_policy: BookPolicy,
+    book_depth: Option<usize>,
+    evaluation: EvaluatorKind,
+    //Built once and kept between searches so caches like the pawn table carry over, rebuilt when the evaluation
+    //changes or a new game starts
+    evaluator: Arc<Mutex<Box<dyn Evaluator + Send>>>,
+    depth: usize,
+    age: usize,
+    //The search runs on its own thread so stop can be read while it is thinking
+    search: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl UciState {
+    fn new() -> Self {
+        UciState {
+            board: Board::default(),
+            history: History::new(),
+            trans_table: Arc::new(Mutex::new(TranspositionTable::default())),
+            books: Arc::new(OpeningBook::load_list(DEFAULT_BOOK).unwrap()),
+            own_book: true,
+            book_policy: BookPolicy::WeightedRandom,
+            book_depth: None,
+            evaluation: EvaluatorKind::Standard,
+            evaluator: Arc::new(Mutex::new(EvaluatorKind::Standard.build())),
+            depth: DEFAULT_DEPTH,
+            age: 0,
+            search: None,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+pub fn start_uci() {
+    let mut state = UciState::new();
+
+    for line in stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"uci") => {
+                println!("id name Cringe Bot");
+                println!("id author Dhhoyt");
+                println!(
+                    "option name Depth type spin default {} min 1 max 64",
+                    DEFAULT_DEPTH
+                );
+                println!(
+                    "option name Hash type spin default {} min 1 max 4096",
+                    DEFAULT_HASH_MB
+                );
+                println!("option name OwnBook type check default true");
+                //Names of bundled books or paths to Polyglot files, separated by semicolons
+                println!("option name Book type string default {}", DEFAULT_BOOK);
+                println!("option name BookPolicy type combo default WeightedRandom var BestWeight var WeightedRandom var Uniform");
+                println!("option name BookDepth type spin default 0 min 0 max 200");
+                println!("option name Evaluation type combo default Standard var Standard var Material var Weak");
+                println!("option name Clear Hash type button");
+                println!("uciok");
+            }
+            Some(&"isready") => println!("readyok"),
+            Some(&"ucinewgame") => {
+                wait_for_search(&mut state);
+                state.board = Board::default();
+                state.history.clear();
+                state.trans_table.lock().unwrap().clear();
+                *state.evaluator.lock().unwrap() = state.evaluation.build();
+                state.age = 0;
+            }
+            Some(&"position") => {
+                wait_for_search(&mut state);
+                set_position(&mut state, &tokens[1..]);
+            }
+            Some(&"go") => {
+                wait_for_search(&mut state);
+                go(&mut state, &tokens[1..]);
+            }
+            Some(&"setoption") => {
+                wait_for_search(&mut state);
+                set_option(&mut state, &tokens[1..]);
+            }
+            Some(&"stop") => stop_search(&mut state),
+            Some(&"quit") => {
+                stop_search(&mut state);
+                break;
+            }
+            _ => (),
+        }
+    }
+    stop_search(&mut state);
+}
+
+fn wait_for_search(state: &mut UciState) {
+    if let Some(search) = state.search.take() {
+        search.join().unwrap();
+    }
+}
+
+fn stop_search(state: &mut UciState) {
+    state.stop.store(true, Ordering::Relaxed);
+    wait_for_search(state);
+}
+
+fn set_position(state: &mut UciState, tokens: &[&str]) {
+    let moves_index = tokens
+        .iter()
+        .position(|x| *x == "moves")
+        .unwrap_or(tokens.len());
+    let board = match tokens.first() {
+        Some(&"startpos") => Board::default(),
+        Some(&"fen") => {
+            let mut fen = tokens[1..moves_index].join(" ");
+            //Move counters are optional in a lot of GUIs
+            if moves_index == 5 {
+                fen.push_str(" 0 1");
+            }
+            match Board::from_fen(&fen) {
+                Ok(board) => board,
+                Err(err) => {
+                    println!("info string invalid fen {:?}", err);
+                    return;
+                }
+            }
+        }
+        _ => return,
+    };
+    //Nothing changes unless every move is good, a position the GUI never sent is never searched
+    let mut board = board;
+    let mut history = History::new();
+    for text in tokens.iter().skip(moves_index + 1) {
+        match Move::from_uci(text) {
+            //make_move trusts the move it is given, anything else would corrupt the board
+            Some(uci_move) if board.legal_moves().contains(&uci_move) => {
+                history.push(board.zobrist());
+                board = board.make_move(uci_move);
+            }
+            Some(_) => {
+                println!("info string illegal move {}", text);
+                return;
+            }
+            None => {
+                println!("info string invalid move {}", text);
+                return;
+            }
+        }
+    }
+    state.board = board;
+    state.history = history;
+}
+
+fn go(state: &mut UciState, tokens: &[&str]) {
+    if tokens.first() == Some(&"perft") {
+        if let Some(depth) = tokens.get(1).and_then(|x| x.parse().ok()) {
+            perft(&state.board, depth);
+        }
+        return;
+    }
+
+    let limits = parse_limits(state, tokens);
+    state.stop = limits.stop.clone();
+
+    let board = state.board;
+    let mut history = state.history.clone();
+    let trans_table = state.trans_table.clone();
+    let books = if state.own_book {
+        state.books.clone()
+    } else {
+        Arc::new(Vec::new())
+    };
+    let evaluator = state.evaluator.clone();
+    let age = state.age;
+    state.age += 1;
+
+    state.search = Some(thread::spawn(move || {
+        let found = board.find_move(
+            &limits,
+            age,
+            &mut trans_table.lock().unwrap(),
+            &books,
+            &mut history,
+            &mut *evaluator.lock().unwrap(),
+            |info| print_info(info, board.white_to_play),
+        );
+        if found.book_dropped > 0 {
+            println!(
+                "info string skipped {} illegal book entries",
+                found.book_dropped
+            );
+        }
+        match found.best_move {
+            Some(best_move) => {
+                if found.from_book {
+                    println!("info string book move");
+                }
+                println!("bestmove {}", best_move.to_uci());
+            }
+            None => println!("bestmove 0000"),
+        }
+    }));
+}
+
+fn print_info(info: &SearchInfo, white_to_play: bool) {
+    //The search scores from white's point of view, UCI wants it from the side to move
+    let score = if white_to_play {
+        info.score
+    } else {
+        -info.score
+    };
+    let score = match mate_in(score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", score),
+    };
+    let pv: Vec<String> = info.pv.iter().map(|m| m.to_uci()).collect();
+    println!(
+        "info depth {} score {} nodes {} nps {} time {} pv {}",
+        info.depth,
+        score,
+        info.nodes,
+        info.nps(),
+        info.time.as_millis(),
+        pv.join(" ")
+    );
+}
+
+fn parse_limits(state: &UciState, tokens: &[&str]) -> SearchLimits {
+    let mut depth = None;
+    let mut move_time = None;
+    let mut infinite = false;
+    let mut white_time = None;
+    let mut black_time = None;
+    let mut white_increment = 0;
+    let mut black_increment = 0;
+    let mut moves_to_go = None;
+
+    let mut tokens = tokens.iter().peekable();
+    while let Some(token) = tokens.next() {
+        match *token {
+            "infinite" => infinite = true,
+            //Searching is the same whether or not it is on the opponent's time
+            "ponder" => (),
+            //Restricting the root moves isn't supported, the moves run up to the next keyword
+            "searchmoves" => while tokens.next_if(|x| Move::from_uci(x).is_some()).is_some() {},
+            "depth" | "movetime" | "wtime" | "btime" | "winc" | "binc" | "movestogo" | "nodes"
+            | "mate" => {
+                let value = match tokens.next_if(|x| x.parse::<u64>().is_ok()) {
+                    Some(value) => value.parse::<u64>().unwrap(),
+                    None => continue,
+                };
+                match *token {
+                    "depth" => depth = Some(value as usize),
+                    "movetime" => move_time = Some(value),
+                    "wtime" => white_time = Some(value),
+                    "btime" => black_time = Some(value),
+                    "winc" => white_increment = value,
+                    "binc" => black_increment = value,
+                    "movestogo" => moves_to_go = Some(value as u32),
+                    //Node and mate limits are read so they don't throw the rest off, the search has neither
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let (time_left, increment) = if state.board.white_to_play {
+        (white_time, white_increment)
+    } else {
+        (black_time, black_increment)
+    };
+    let mut limits = if infinite {
+        SearchLimits::infinite()
+    } else if let Some(move_time) = move_time {
+        SearchLimits::move_time(Duration::from_millis(move_time))
+    } else if let Some(time_left) = time_left {
+        SearchLimits::clock(
+            Duration::from_millis(time_left),
+            Duration::from_millis(increment),
+            moves_to_go,
+        )
+    } else {
+        SearchLimits::depth(state.depth)
+    };
+    if let Some(depth) = depth {
+        limits.depth = depth.max(1);
+    }
+    limits
+}
+
+fn perft(board: &Board, depth: usize) {
+    let mut total = 0;
+    for (root_move, nodes) in board.divide(depth) {
+        println!("{}: {}", root_move.to_uci(), nodes);
+        total += nodes;
+    }
+    println!();
+    println!("Nodes searched: {}", total);
+}
+
+fn set_option(state: &mut UciState, tokens: &[&str]) {
+    let value_index = tokens
+        .iter()
+        .position(|x| *x == "value")
+        .unwrap_or(tokens.len());
+    if tokens.first() != Some(&"name") {
+        return;
+    }
+    let name = tokens[1..value_index].join(" ");
+    //Paths can have spaces in them
+    let value = tokens.get(value_index + 1..).unwrap_or(&[]).join(" ");
+    let value = value.as_str();
+    match name.to_lowercase().as_str() {
+        "depth" => {
+            if let Ok(depth) = value.parse::<usize>() {
+                state.depth = depth.max(1);
+            }
+        }
+        "hash" => {
+            if let Ok(size_mb) = value.parse::<usize>() {
+                state.trans_table.lock().unwrap().resize(size_mb);
+            }
+        }
+        "ownbook" => state.own_book = value == "true",
+        "book" => match OpeningBook::load_list(value) {
+            Ok(books) => {
+                state.books = Arc::new(books);
+                update_books(state);
+            }
+            Err(err) => println!("info string {}", err),
+        },
+        "bookpolicy" => match BookPolicy::from_name(value) {
+            Some(policy) => {
+                state.book_policy = policy;
+                update_books(state);
+            }
+            None => println!("info string unknown book policy {}", value),
+        },
+        "bookdepth" => {
+            if let Ok(depth) = value.parse::<usize>() {
+                //0 means the books are used for as long as they have moves
+                state.book_depth = if depth == 0 { None } else { Some(depth) };
+                update_books(state);
+            }
+        }
+        "evaluation" => match EvaluatorKind::from_name(value) {
+            Some(evaluation) if evaluation != state.evaluation => {
+                state.evaluation = evaluation;
+                *state.evaluator.lock().unwrap() = evaluation.build();
+            }
+            Some(_) => (),
+            None => println!("info string unknown evaluation {}", value),
+        },
+        "clear hash" => state.trans_table.lock().unwrap().clear(),
+        _ => println!("info string unknown option {}", name),
+    }
+}
+
+fn update_books(state: &mut UciState) {
+    for book in Arc::make_mut(&mut state.books) {
+        book.policy = state.book_policy;
+        book.max_depth = state.book_depth;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_limits, set_position, Board, Move, UciState};
+
+    #[test]
+    fn go_tokens() {
+        let state = UciState::new();
+        let limits = |text: &str| {
+            let tokens: Vec<&str> = text.split_whitespace().collect();
+            parse_limits(&state, &tokens)
+        };
+        for text in [
+            "ponder wtime 60000 btime 60000",
+            "searchmoves e2e4 d2d4 wtime 60000 btime 60000",
+            "nodes 5000 mate 3 wtime 60000 btime 60000 winc 1000 binc 1000",
+        ] {
+            assert!(limits(text).soft_deadline.is_some(), "{}", text);
+        }
+        let searchmoves = limits("searchmoves e2e4 g1f3 depth 5 movetime 1000");
+        assert_eq!(searchmoves.depth, 5);
+        assert!(searchmoves.hard_deadline.is_some());
+        assert!(limits("ponder infinite").infinite);
+        let depth = limits("depth 4 nodes 100");
+        assert_eq!(depth.depth, 4);
+        assert!(depth.soft_deadline.is_none() & !depth.infinite);
+    }
+
+    #[test]
+    fn bad_moves_keep_the_position() {
+        let mut state = UciState::new();
+        let mut position = |text: &str| {
+            let tokens: Vec<&str> = text.split_whitespace().collect();
+            set_position(&mut state, &tokens);
+            state.board.to_fen()
+        };
+        let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        assert_eq!(position("startpos moves e2e4"), after_e4);
+        assert_eq!(position("startpos moves e2e4 e7e5 e1e3"), after_e4);
+        assert_eq!(position("startpos moves g1f3 g8f6 zz"), after_e4);
+        assert_eq!(position("fen 8/8/8 w - - 0 1"), after_e4);
+        //The history holds the start position and nothing from the moves that were thrown away
+        let start = Board::default();
+        let after_nf3 = start.make_move(Move::from_uci("g1f3").unwrap());
+        assert!(state.history.is_repetition(start.zobrist(), 100));
+        assert!(!state.history.is_repetition(after_nf3.zobrist(), 100));
+    }
+}